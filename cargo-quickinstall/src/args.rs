@@ -1,4 +1,16 @@
-use std::cmp::Ordering;
+use std::{
+    cmp::Ordering, collections::BTreeMap, ffi::OsString, fmt::Display, path::PathBuf, str::FromStr,
+};
+
+use cargo_quickinstall::Verbosity;
+
+use crate::config::{Config, Source};
+
+/// How many HEAD requests `info` and `--dry-run --target all` do at once by default.
+const DEFAULT_JOBS: usize = 8;
+
+/// Anything past `-vv` is the same as `-vv`, but we accept it anyway.
+const VERBOSE_FLAGS: [&str; 4] = ["-v", "-vv", "-vvv", "-vvvv"];
 
 pub const USAGE: &str = "USAGE:
     cargo quickinstall [OPTIONS] -- <CRATES> ...
@@ -7,10 +19,28 @@ For more information try --help
 ";
 pub const HELP: &str = "USAGE:
     cargo quickinstall [OPTIONS] -- <CRATES> ...
+    cargo quickinstall [OPTIONS] info <CRATES> ...
+    cargo quickinstall [OPTIONS] request <CRATES> ...
+    cargo quickinstall [OPTIONS] config show
 
 <CRATES> ... - can be one or more crates. Each one can be either simply a name, or a name and
                version `cargo-quickinstall@0.2.7`.
 
+SUBCOMMANDS:
+    info                            Show which of the most recent versions of <CRATES> have a
+                                    pre-built package for each target, without installing them.
+                                    Use --target to only check some of the targets.
+
+    request                         Ask for pre-built packages of <CRATES> to be built, for the
+                                    current target or the ones given with --target. This is sent
+                                    to the stats server even with --no-telemetry.
+
+    config show                     Print the effective settings, and whether each of them came
+                                    from the command line, the config file or the defaults.
+
+                                    To install a crate that is named like a subcommand, put it
+                                    after `--`.
+
 OPTIONS:
         --version <VERSION>         Specify a version to install. It's invalid specify this in
                                     batch installtion mode.
@@ -19,9 +49,13 @@ OPTIONS:
                                     to install, then --version takes precedence over the version
                                     specified in the <CRATES>.
 
-        --target <TRIPLE>           Install package for the target triple
+        --target <TRIPLE>           Install package for the target triple.
+                                    With --dry-run, this can also be `all` or a comma-separated
+                                    list of triples, to print which of them have a pre-built
+                                    package.
         --force                     Install the <CRATES> even if they are already installed.
-                                    Only effective when using `cargo-binstall`.
+                                    With --no-binstall, this also allows overwriting binaries
+                                    that belong to other crates, or that we didn't install.
         --try-upstream              Try looking for official builds from the upstream maintainers.
                                     This takes a few extra seconds.
         --no-fallback               Don't fall back to `cargo install`
@@ -30,13 +64,98 @@ OPTIONS:
                                     `--no-binstall` if you know you don't need to uninstall packages
                                     (for example on CI builds).
         --dry-run                   Print the `curl | tar` command that would be run to fetch the binary
+        --list-files                Print the files in the pre-built package of each of <CRATES>,
+                                    with their sizes and modes, without installing anything
+        --mirror <URL>              Download pre-built packages from <URL> instead of our GitHub
+                                    releases. It must have the same layout as
+                                    https://github.com/cargo-bins/cargo-quickinstall/releases/download
+        --no-telemetry              Don't report installs to our stats server. We use the reports
+                                    to decide which packages to build. This is also passed on to
+                                    `cargo-binstall` as `--disable-telemetry`.
+        --print-telemetry           Print exactly what is reported to our stats server for each
+                                    install, or would be with --no-telemetry. Requires
+                                    --no-binstall, since `cargo-binstall` sends its own reports.
+        --stats-server <URL>        Report installs to the stats server at <URL> instead of ours,
+                                    for example if you run your own copy of `stats-server`.
+                                    `cargo-binstall` can't be pointed at it, so this also passes
+                                    it `--disable-telemetry`.
+        --jobs <N>                  How many packages to check for at once in `info` and
+                                    `--dry-run --target all` [default: 8]
+        --retries <N>               How many times to retry a download that failed with a 5xx,
+                                    a 429 or a connection error, waiting a little longer each
+                                    time. 404s are never retried. [default: 3]
+        --proxy <URL>               Make every request through the proxy at <URL>, including the
+                                    ones made by `cargo-binstall` and `cargo install`. By default,
+                                    they use $https_proxy, $http_proxy or $all_proxy.
+        --no-proxy <HOSTS>          Comma-separated list of hosts to connect to directly, without
+                                    the proxy. By default, $no_proxy is used.
+        --ca-cert <FILE>            Also trust the CA certificates in <FILE>, in PEM format, on top
+                                    of the system ones. We combine them into
+                                    $CARGO_HOME/.quickinstall-ca-bundle.pem.
+        --connect-timeout <SECS>    Give up on connecting to a server after <SECS> seconds
+        --timeout <SECS>            Give up on each request after <SECS> seconds altogether
+        --max-download-size <BYTES> Refuse to download packages bigger than <BYTES>
+    -q, --quiet                     Only print errors
+    -v, --verbose                   Also print every url we request and how long each step took.
+                                    Use -vv to also print every command we run.
     -V, --print-version             Print version info and exit
     -h, --help                      Prints help information
+
+ENVIRONMENT:
+    These can be used instead of the options of the same name. Flags accept 1, true, yes or on,
+    and 0, false, no or off. Options on the command line take precedence.
+
+        CARGO_QUICKINSTALL_TARGET=<TRIPLE>
+        CARGO_QUICKINSTALL_FORCE=<BOOL>
+        CARGO_QUICKINSTALL_TRY_UPSTREAM=<BOOL>
+        CARGO_QUICKINSTALL_NO_FALLBACK=<BOOL>
+        CARGO_QUICKINSTALL_NO_BINSTALL=<BOOL>
+        CARGO_QUICKINSTALL_DRY_RUN=<BOOL>
+        CARGO_QUICKINSTALL_NO_TELEMETRY=<BOOL>
+        CARGO_QUICKINSTALL_MIRROR=<URL>
+        CARGO_QUICKINSTALL_STATS_SERVER=<URL>
+        CARGO_QUICKINSTALL_JOBS=<N>
+        CARGO_QUICKINSTALL_RETRIES=<N>
+        CARGO_QUICKINSTALL_PROXY=<URL>
+        CARGO_QUICKINSTALL_NO_PROXY=<HOSTS>
+        CARGO_QUICKINSTALL_CA_CERT=<FILE>
+        CARGO_QUICKINSTALL_CONNECT_TIMEOUT=<SECS>
+        CARGO_QUICKINSTALL_TIMEOUT=<SECS>
+        CARGO_QUICKINSTALL_MAX_DOWNLOAD_SIZE=<BYTES>
+        CARGO_QUICKINSTALL_QUIET=<BOOL>
+
+        GH_TOKEN=<TOKEN>, GITHUB_TOKEN=<TOKEN>
+                                    Sent to github.com when downloading packages, to avoid
+                                    GitHub's rate limits for anonymous downloads on shared CI
+                                    runners. It is never sent anywhere else, including mirrors.
+
+CONFIG FILE:
+    Defaults for the options can be set in $CARGO_HOME/quickinstall.toml, or in the file named by
+    $CARGO_QUICKINSTALL_CONFIG. Options on the command line and in the environment take
+    precedence. For example:
+
+        target = \"x86_64-unknown-linux-musl\"
+        fallback = false                    # like --no-fallback
+        binstall = false                    # like --no-binstall
+        mirror = \"https://example.com/cargo-quickinstall\"
+        telemetry = false                   # don't report installs to our stats server
+        stats_server = \"https://stats.example.com\"
+        jobs = 4
+        retries = 5
+        github_token = \"ghp_...\"            # if $GH_TOKEN and $GITHUB_TOKEN aren't set
+        proxy = \"http://proxy.example.com:3128\"
+        no_proxy = \"localhost,.internal.example.com\"
+        ca_cert = \"/etc/ssl/certs/corporate-ca.pem\"
+        connect_timeout = 10
+        timeout = 300
+        max_download_size = 104857600       # 100 MiB
+        quiet = true                        # like --quiet
 ";
 
 #[cfg_attr(test, derive(Debug))]
 pub struct CliOptions {
     pub target: Option<String>,
+    pub subcommand: Option<Subcommand>,
     pub crate_names: Vec<Crate>,
     pub try_upstream: bool,
     pub fallback: bool,
@@ -45,6 +164,43 @@ pub struct CliOptions {
     pub print_version: bool,
     pub help: bool,
     pub dry_run: bool,
+    pub list_files: bool,
+    pub mirror: Option<String>,
+    pub telemetry: bool,
+    pub print_telemetry: bool,
+    pub stats_server: Option<String>,
+    pub jobs: usize,
+    pub retries: usize,
+    pub github_token: Option<String>,
+    pub proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    pub ca_cert: Option<PathBuf>,
+    pub connect_timeout: Option<u64>,
+    pub timeout: Option<u64>,
+    pub max_download_size: Option<u64>,
+    pub verbosity: Verbosity,
+    /// The config file that the defaults were read from, if any.
+    pub config_path: Option<PathBuf>,
+    /// Where each of the settings in the config file came from.
+    pub sources: BTreeMap<&'static str, Source>,
+}
+
+#[cfg_attr(test, derive(Debug, Eq, PartialEq))]
+pub enum Subcommand {
+    Info,
+    Request,
+    ConfigShow,
+}
+
+impl Subcommand {
+    fn new(s: &str) -> Option<Self> {
+        match s {
+            "info" => Some(Self::Info),
+            "request" => Some(Self::Request),
+            "config" => Some(Self::ConfigShow),
+            _ => None,
+        }
+    }
 }
 
 #[cfg_attr(test, derive(Debug, Eq, PartialEq))]
@@ -83,24 +239,313 @@ impl Crate {
 }
 
 pub fn options_from_cli_args(
+    args: pico_args::Arguments,
+) -> Result<CliOptions, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    options_from_cli_args_env_and_config(args, &|name| std::env::var_os(name), Config::load)
+}
+
+/// Looks up an environment variable.
+type Env<'a> = &'a dyn Fn(&str) -> Option<OsString>;
+
+/// Parse the `CARGO_QUICKINSTALL_*` environment variable `name`. Empty values
+/// are treated as if the variable was not set.
+fn env_value<T>(env: Env<'_>, name: &str) -> Result<Option<T>, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = match env(name) {
+        Some(value) if !value.is_empty() => value,
+        _ => return Ok(None),
+    };
+    let value = value
+        .into_string()
+        .map_err(|_| format!("environment variable {name} is not valid UTF-8"))?;
+
+    value
+        .parse()
+        .map(Some)
+        .map_err(|err| format!("failed to parse environment variable {name}='{value}': {err}"))
+}
+
+/// Like [`env_value`], but for environment variables that stand in for `--flags`.
+fn env_flag(env: Env<'_>, name: &str) -> Result<Option<bool>, String> {
+    let value = env_value::<String>(env, name)?;
+
+    match value.as_deref() {
+        None => Ok(None),
+        Some("1" | "true" | "yes" | "on") => Ok(Some(true)),
+        Some("0" | "false" | "no" | "off") => Ok(Some(false)),
+        Some(value) => Err(format!(
+            "failed to parse environment variable {name}='{value}': expected one of 1, true, yes, on, 0, false, no or off"
+        )),
+    }
+}
+
+/// Returns the value from the command line if there is one, or else the value
+/// from the environment, or else the value from the config file, and records
+/// which one it was in `sources`.
+fn merge<T>(
+    sources: &mut BTreeMap<&'static str, Source>,
+    key: &'static str,
+    cli: Option<T>,
+    (env_name, env): (&'static str, Option<T>),
+    config: Option<T>,
+) -> Option<T> {
+    let (value, source) = match (cli, env, config) {
+        (Some(value), _, _) => (Some(value), Source::CommandLine),
+        (None, Some(value), _) => (Some(value), Source::EnvVar(env_name)),
+        (None, None, Some(value)) => (Some(value), Source::ConfigFile),
+        (None, None, None) => (None, Source::Default),
+    };
+    sources.insert(key, source);
+    value
+}
+
+fn options_from_cli_args_env_and_config(
     mut args: pico_args::Arguments,
+    env: Env<'_>,
+    load_config: impl FnOnce() -> Result<Config, Box<dyn std::error::Error + Send + Sync + 'static>>,
 ) -> Result<CliOptions, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    // `--help` and `--print-version` don't need the config file, so they should
+    // still work if it's broken.
+    let print_version = args.contains(["-V", "--print-version"]);
+    let help = args.contains(["-h", "--help"]);
+    let config = if print_version || help {
+        Config::default()
+    } else {
+        load_config()?
+    };
+
     let version = args.opt_value_from_str("--version")?;
 
+    let mut sources = BTreeMap::new();
+
+    // Negative flags like `--no-fallback` can only turn things off, but the
+    // matching environment variables can be set to either value.
+    let no_fallback = "CARGO_QUICKINSTALL_NO_FALLBACK";
+    let no_binstall = "CARGO_QUICKINSTALL_NO_BINSTALL";
+    let no_telemetry = "CARGO_QUICKINSTALL_NO_TELEMETRY";
+
+    // The GitHub token can't be passed on the command line, so that it doesn't show
+    // up in `ps`. We use the same variables as the `gh` cli, in the same order.
+    let github_token_env = match env_value(env, "GH_TOKEN")? {
+        Some(token) => ("GH_TOKEN", Some(token)),
+        None => ("GITHUB_TOKEN", env_value(env, "GITHUB_TOKEN")?),
+    };
+
+    // pico-args doesn't split up combined short flags, so `-vv` and friends are
+    // flags of their own.
+    let cli_quiet = args.contains(["-q", "--quiet"]);
+    let mut verbose = 0;
+    for (count, flag) in VERBOSE_FLAGS.iter().enumerate() {
+        while args.contains(*flag) {
+            verbose += count + 1;
+        }
+    }
+    while args.contains("--verbose") {
+        verbose += 1;
+    }
+    if cli_quiet && verbose > 0 {
+        Err("`--quiet` and `--verbose` cannot be used together")?
+    }
+    // `-v` on the command line wins over `quiet` in the environment or config file.
+    let quiet = merge(
+        &mut sources,
+        "quiet",
+        if cli_quiet {
+            Some(true)
+        } else {
+            (verbose > 0).then_some(false)
+        },
+        (
+            "CARGO_QUICKINSTALL_QUIET",
+            env_flag(env, "CARGO_QUICKINSTALL_QUIET")?,
+        ),
+        config.quiet,
+    )
+    .unwrap_or(false);
+    let verbosity = match verbose {
+        0 if quiet => Verbosity::Quiet,
+        0 => Verbosity::Normal,
+        1 => Verbosity::Verbose,
+        _ => Verbosity::VeryVerbose,
+    };
+
+    // WARNING: We MUST parse all --options before parsing positional arguments,
+    // because .subcommand() errors out if handed an arg with - at the start.
     let mut opts = CliOptions {
-        target: args.opt_value_from_str("--target")?,
-        try_upstream: args.contains("--try-upstream"),
-        fallback: !args.contains("--no-fallback"),
-        force: args.contains("--force"),
-        no_binstall: args.contains("--no-binstall"),
-        print_version: args.contains(["-V", "--print-version"]),
-        help: args.contains(["-h", "--help"]),
-        dry_run: args.contains("--dry-run"),
-        // WARNING: We MUST parse all --options before parsing positional arguments,
-        // because .subcommand() errors out if handed an arg with - at the start.
-        crate_names: crate_names_from_positional_args(args)?,
+        target: merge(
+            &mut sources,
+            "target",
+            args.opt_value_from_str("--target")?,
+            (
+                "CARGO_QUICKINSTALL_TARGET",
+                env_value(env, "CARGO_QUICKINSTALL_TARGET")?,
+            ),
+            config.target,
+        ),
+        try_upstream: args.contains("--try-upstream")
+            || env_flag(env, "CARGO_QUICKINSTALL_TRY_UPSTREAM")?.unwrap_or(false),
+        fallback: merge(
+            &mut sources,
+            "fallback",
+            args.contains("--no-fallback").then_some(false),
+            (no_fallback, env_flag(env, no_fallback)?.map(|no| !no)),
+            config.fallback,
+        )
+        .unwrap_or(true),
+        force: args.contains("--force")
+            || env_flag(env, "CARGO_QUICKINSTALL_FORCE")?.unwrap_or(false),
+        no_binstall: !merge(
+            &mut sources,
+            "binstall",
+            args.contains("--no-binstall").then_some(false),
+            (no_binstall, env_flag(env, no_binstall)?.map(|no| !no)),
+            config.binstall,
+        )
+        .unwrap_or(true),
+        print_version,
+        help,
+        dry_run: args.contains("--dry-run")
+            || env_flag(env, "CARGO_QUICKINSTALL_DRY_RUN")?.unwrap_or(false),
+        list_files: args.contains("--list-files"),
+        mirror: merge(
+            &mut sources,
+            "mirror",
+            args.opt_value_from_str("--mirror")?,
+            (
+                "CARGO_QUICKINSTALL_MIRROR",
+                env_value(env, "CARGO_QUICKINSTALL_MIRROR")?,
+            ),
+            config.mirror,
+        ),
+        telemetry: merge(
+            &mut sources,
+            "telemetry",
+            args.contains("--no-telemetry").then_some(false),
+            (no_telemetry, env_flag(env, no_telemetry)?.map(|no| !no)),
+            config.telemetry,
+        )
+        .unwrap_or(true),
+        print_telemetry: args.contains("--print-telemetry"),
+        stats_server: merge(
+            &mut sources,
+            "stats_server",
+            args.opt_value_from_str("--stats-server")?,
+            (
+                "CARGO_QUICKINSTALL_STATS_SERVER",
+                env_value(env, "CARGO_QUICKINSTALL_STATS_SERVER")?,
+            ),
+            config.stats_server,
+        ),
+        jobs: merge(
+            &mut sources,
+            "jobs",
+            args.opt_value_from_str("--jobs")?,
+            (
+                "CARGO_QUICKINSTALL_JOBS",
+                env_value(env, "CARGO_QUICKINSTALL_JOBS")?,
+            ),
+            config.jobs,
+        )
+        .unwrap_or(DEFAULT_JOBS),
+        retries: merge(
+            &mut sources,
+            "retries",
+            args.opt_value_from_str("--retries")?,
+            (
+                "CARGO_QUICKINSTALL_RETRIES",
+                env_value(env, "CARGO_QUICKINSTALL_RETRIES")?,
+            ),
+            config.retries,
+        )
+        .unwrap_or(cargo_quickinstall::DEFAULT_RETRIES),
+        github_token: merge(
+            &mut sources,
+            "github_token",
+            None,
+            github_token_env,
+            config.github_token,
+        )
+        .map(|token: String| token.trim().to_string())
+        .filter(|token| !token.is_empty()),
+        proxy: merge(
+            &mut sources,
+            "proxy",
+            args.opt_value_from_str("--proxy")?,
+            (
+                "CARGO_QUICKINSTALL_PROXY",
+                env_value(env, "CARGO_QUICKINSTALL_PROXY")?,
+            ),
+            config.proxy,
+        ),
+        no_proxy: merge(
+            &mut sources,
+            "no_proxy",
+            args.opt_value_from_str("--no-proxy")?,
+            (
+                "CARGO_QUICKINSTALL_NO_PROXY",
+                env_value(env, "CARGO_QUICKINSTALL_NO_PROXY")?,
+            ),
+            config.no_proxy,
+        ),
+        ca_cert: merge(
+            &mut sources,
+            "ca_cert",
+            args.opt_value_from_str("--ca-cert")?,
+            (
+                "CARGO_QUICKINSTALL_CA_CERT",
+                env_value(env, "CARGO_QUICKINSTALL_CA_CERT")?,
+            ),
+            config.ca_cert,
+        ),
+        connect_timeout: merge(
+            &mut sources,
+            "connect_timeout",
+            args.opt_value_from_str("--connect-timeout")?,
+            (
+                "CARGO_QUICKINSTALL_CONNECT_TIMEOUT",
+                env_value(env, "CARGO_QUICKINSTALL_CONNECT_TIMEOUT")?,
+            ),
+            config.connect_timeout,
+        ),
+        timeout: merge(
+            &mut sources,
+            "timeout",
+            args.opt_value_from_str("--timeout")?,
+            (
+                "CARGO_QUICKINSTALL_TIMEOUT",
+                env_value(env, "CARGO_QUICKINSTALL_TIMEOUT")?,
+            ),
+            config.timeout,
+        ),
+        max_download_size: merge(
+            &mut sources,
+            "max_download_size",
+            args.opt_value_from_str("--max-download-size")?,
+            (
+                "CARGO_QUICKINSTALL_MAX_DOWNLOAD_SIZE",
+                env_value(env, "CARGO_QUICKINSTALL_MAX_DOWNLOAD_SIZE")?,
+            ),
+            config.max_download_size,
+        ),
+        verbosity,
+        config_path: config.path,
+        sources,
+        subcommand: None,
+        crate_names: Vec::new(),
     };
 
+    if opts.jobs == 0 {
+        Err("`--jobs` must be at least 1")?
+    }
+    if opts.connect_timeout == Some(0) || opts.timeout == Some(0) {
+        Err("`--connect-timeout` and `--timeout` must be at least 1 second")?
+    }
+
+    (opts.subcommand, opts.crate_names) = subcommand_and_crate_names_from_positional_args(args)?;
+
     if version.is_some() {
         match opts.crate_names.len().cmp(&1) {
             Ordering::Equal => opts.crate_names[0].version = version,
@@ -112,9 +557,33 @@ pub fn options_from_cli_args(
     Ok(opts)
 }
 
-pub fn crate_names_from_positional_args(
+/// Returns the list of targets if `--target` names more than one of them,
+/// either as `all` or as a comma-separated list.
+pub fn multiple_targets(target: &str) -> Option<Vec<String>> {
+    if target == "all" {
+        Some(
+            cargo_quickinstall::SUPPORTED_TARGETS
+                .iter()
+                .map(|target| target.to_string())
+                .collect(),
+        )
+    } else if target.contains(',') {
+        Some(
+            target
+                .split(',')
+                .map(str::trim)
+                .filter(|target| !target.is_empty())
+                .map(str::to_string)
+                .collect(),
+        )
+    } else {
+        None
+    }
+}
+
+pub fn subcommand_and_crate_names_from_positional_args(
     args: pico_args::Arguments,
-) -> Result<Vec<Crate>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+) -> Result<(Option<Subcommand>, Vec<Crate>), Box<dyn std::error::Error + Send + Sync + 'static>> {
     let args = args.finish();
 
     let args = args
@@ -128,7 +597,7 @@ pub fn crate_names_from_positional_args(
 
     let mut check_for_slash = true;
 
-    let args_to_skip = match args[..] {
+    let mut args_to_skip = match args[..] {
         ["quickinstall", "--", ..] => {
             check_for_slash = false;
             2
@@ -141,7 +610,30 @@ pub fn crate_names_from_positional_args(
         _ => 0,
     };
 
-    args.into_iter()
+    // Anything after `--` is a crate name, even if it looks like a subcommand.
+    let subcommand = if check_for_slash {
+        args.get(args_to_skip).copied().and_then(Subcommand::new)
+    } else {
+        None
+    };
+    if subcommand.is_some() {
+        args_to_skip += 1;
+
+        if matches!(subcommand, Some(Subcommand::ConfigShow)) {
+            match args.get(args_to_skip) {
+                Some(&"show") => args_to_skip += 1,
+                _ => Err("Expected `cargo quickinstall config show`")?,
+            }
+        }
+
+        if args.get(args_to_skip) == Some(&"--") {
+            check_for_slash = false;
+            args_to_skip += 1;
+        }
+    }
+
+    let crate_names = args
+        .into_iter()
         .skip(args_to_skip)
         .map(|arg| {
             if check_for_slash && arg.starts_with('-') {
@@ -150,7 +642,9 @@ pub fn crate_names_from_positional_args(
                 Ok(Crate::new(arg))
             }
         })
-        .collect()
+        .collect::<Result<_, Box<dyn std::error::Error + Send + Sync + 'static>>>()?;
+
+    Ok((subcommand, crate_names))
 }
 
 #[cfg(test)]
@@ -198,12 +692,236 @@ mod test {
         assert!(result.is_err(), "{:#?}", result);
     }
 
+    #[test]
+    fn test_options_from_config() {
+        let mock_cli_args: Vec<OsString> = [MOCK_CRATE_NAME, "--target", "aarch64-apple-darwin"]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        let mock_pico_args = pico_args::Arguments::from_vec(mock_cli_args);
+
+        let config = Config {
+            path: Some(PathBuf::from("quickinstall.toml")),
+            target: Some("x86_64-unknown-linux-musl".to_string()),
+            binstall: Some(false),
+            ..Default::default()
+        };
+
+        let cli_options =
+            options_from_cli_args_env_and_config(mock_pico_args, &|_| None, || Ok(config)).unwrap();
+
+        assert_eq!(cli_options.target.as_deref(), Some("aarch64-apple-darwin"));
+        assert_eq!(cli_options.sources["target"], Source::CommandLine);
+        assert!(cli_options.no_binstall);
+        assert_eq!(cli_options.sources["binstall"], Source::ConfigFile);
+        assert!(cli_options.fallback);
+        assert_eq!(cli_options.sources["fallback"], Source::Default);
+        assert_eq!(cli_options.retries, cargo_quickinstall::DEFAULT_RETRIES);
+    }
+
+    #[test]
+    fn test_options_from_env_vars() {
+        let mock_cli_args: Vec<OsString> = [MOCK_CRATE_NAME, "--jobs", "2"]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        let mock_pico_args = pico_args::Arguments::from_vec(mock_cli_args);
+
+        let env = |name: &str| {
+            match name {
+                "CARGO_QUICKINSTALL_TARGET" => Some("x86_64-unknown-linux-musl"),
+                "CARGO_QUICKINSTALL_NO_BINSTALL" => Some("true"),
+                "CARGO_QUICKINSTALL_FORCE" => Some("1"),
+                "CARGO_QUICKINSTALL_JOBS" => Some("4"),
+                "CARGO_QUICKINSTALL_RETRIES" => Some("0"),
+                "GITHUB_TOKEN" => Some("from-github-token"),
+                "CARGO_QUICKINSTALL_PROXY" => Some("http://proxy.example.com:3128"),
+                "CARGO_QUICKINSTALL_TIMEOUT" => Some("60"),
+                "CARGO_QUICKINSTALL_DRY_RUN" => Some(""),
+                _ => None,
+            }
+            .map(OsString::from)
+        };
+        let config = Config {
+            target: Some("aarch64-apple-darwin".to_string()),
+            ..Default::default()
+        };
+
+        let cli_options =
+            options_from_cli_args_env_and_config(mock_pico_args, &env, || Ok(config)).unwrap();
+
+        assert_eq!(
+            cli_options.target.as_deref(),
+            Some("x86_64-unknown-linux-musl")
+        );
+        assert_eq!(
+            cli_options.sources["target"],
+            Source::EnvVar("CARGO_QUICKINSTALL_TARGET")
+        );
+        assert!(cli_options.no_binstall);
+        assert!(cli_options.force);
+        assert!(!cli_options.dry_run);
+        assert_eq!(cli_options.jobs, 2);
+        assert_eq!(cli_options.sources["jobs"], Source::CommandLine);
+        assert_eq!(cli_options.retries, 0);
+        assert_eq!(
+            cli_options.github_token.as_deref(),
+            Some("from-github-token")
+        );
+        assert_eq!(
+            cli_options.sources["github_token"],
+            Source::EnvVar("GITHUB_TOKEN")
+        );
+        assert_eq!(
+            cli_options.proxy.as_deref(),
+            Some("http://proxy.example.com:3128")
+        );
+        assert_eq!(cli_options.timeout, Some(60));
+        assert_eq!(cli_options.connect_timeout, None);
+    }
+
+    #[test]
+    fn test_options_from_env_vars_err() {
+        let env = |name: &str| {
+            (name == "CARGO_QUICKINSTALL_NO_FALLBACK").then(|| OsString::from("maybe"))
+        };
+
+        let result = options_from_cli_args_env_and_config(
+            pico_args::Arguments::from_vec(vec![OsString::from(MOCK_CRATE_NAME)]),
+            &env,
+            || Ok(Config::default()),
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "failed to parse environment variable CARGO_QUICKINSTALL_NO_FALLBACK='maybe': \
+             expected one of 1, true, yes, on, 0, false, no or off"
+        );
+    }
+
+    #[test]
+    fn test_help_with_broken_config() {
+        let broken_config = || Err("quickinstall.toml:1: expected `key = value`".into());
+
+        let cli_options = options_from_cli_args_env_and_config(
+            pico_args::Arguments::from_vec(vec![OsString::from("--help")]),
+            &|_| None,
+            broken_config,
+        )
+        .unwrap();
+        assert!(cli_options.help);
+
+        let result = options_from_cli_args_env_and_config(
+            pico_args::Arguments::from_vec(vec![OsString::from(MOCK_CRATE_NAME)]),
+            &|_| None,
+            broken_config,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_verbosity() {
+        let options = |args: &[&str], quiet_env: Option<&str>| {
+            let args = args.iter().map(OsString::from).collect();
+            let env = |name: &str| {
+                (name == "CARGO_QUICKINSTALL_QUIET")
+                    .then_some(quiet_env)
+                    .flatten()
+                    .map(OsString::from)
+            };
+            options_from_cli_args_env_and_config(pico_args::Arguments::from_vec(args), &env, || {
+                Ok(Config::default())
+            })
+        };
+        let verbosity = |args: &[&str], quiet_env: Option<&str>| {
+            options(args, quiet_env).map(|options| options.verbosity)
+        };
+
+        assert_eq!(
+            verbosity(&[MOCK_CRATE_NAME], None).unwrap(),
+            Verbosity::Normal
+        );
+        assert_eq!(
+            verbosity(&["-q", MOCK_CRATE_NAME], None).unwrap(),
+            Verbosity::Quiet
+        );
+        let env_options = options(&[MOCK_CRATE_NAME], Some("1")).unwrap();
+        assert_eq!(env_options.verbosity, Verbosity::Quiet);
+        assert_eq!(
+            env_options.sources["quiet"],
+            Source::EnvVar("CARGO_QUICKINSTALL_QUIET")
+        );
+        assert_eq!(verbosity(&["--verbose"], None).unwrap(), Verbosity::Verbose);
+        // `-v` on the command line wins over `$CARGO_QUICKINSTALL_QUIET`.
+        assert_eq!(verbosity(&["-v"], Some("1")).unwrap(), Verbosity::Verbose);
+        assert_eq!(
+            verbosity(&["-v", "-v"], None).unwrap(),
+            Verbosity::VeryVerbose
+        );
+        assert_eq!(
+            verbosity(&["-vv", "-v"], None).unwrap(),
+            Verbosity::VeryVerbose
+        );
+        assert_eq!(verbosity(&["-vvv"], None).unwrap(), Verbosity::VeryVerbose);
+        assert!(verbosity(&["-q", "-v"], None).is_err());
+    }
+
+    #[test]
+    fn test_subcommand_from_positional_args() {
+        let mock_cli_args: Vec<OsString> = ["quickinstall", "info", MOCK_CRATE_NAME]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        let mock_pico_args = pico_args::Arguments::from_vec(mock_cli_args);
+
+        let (subcommand, crates) =
+            subcommand_and_crate_names_from_positional_args(mock_pico_args).unwrap();
+        assert_eq!(subcommand, Some(Subcommand::Info));
+        assert_eq!(
+            [Crate {
+                name: MOCK_CRATE_NAME.to_string(),
+                version: None
+            }]
+            .as_slice(),
+            crates
+        );
+
+        let mock_cli_args: Vec<OsString> = ["--", "info"].iter().map(OsString::from).collect();
+        let mock_pico_args = pico_args::Arguments::from_vec(mock_cli_args);
+
+        let (subcommand, crates) =
+            subcommand_and_crate_names_from_positional_args(mock_pico_args).unwrap();
+        assert_eq!(subcommand, None);
+        assert_eq!(
+            [Crate {
+                name: "info".to_string(),
+                version: None
+            }]
+            .as_slice(),
+            crates
+        );
+    }
+
+    #[test]
+    fn test_multiple_targets() {
+        assert_eq!(multiple_targets("x86_64-unknown-linux-gnu"), None);
+        assert_eq!(
+            multiple_targets("all").unwrap().len(),
+            cargo_quickinstall::SUPPORTED_TARGETS.len()
+        );
+        assert_eq!(
+            multiple_targets("x86_64-unknown-linux-gnu, aarch64-apple-darwin,").unwrap(),
+            ["x86_64-unknown-linux-gnu", "aarch64-apple-darwin"]
+        );
+    }
+
     #[test]
     fn test_crate_name_from_positional_args() {
         let mock_cli_args: Vec<OsString> = vec![OsString::from(MOCK_CRATE_NAME)];
         let mock_pico_args = pico_args::Arguments::from_vec(mock_cli_args);
 
-        let crate_name = crate_names_from_positional_args(mock_pico_args).unwrap();
+        let (subcommand, crate_name) =
+            subcommand_and_crate_names_from_positional_args(mock_pico_args).unwrap();
+        assert_eq!(subcommand, None);
         assert_eq!(
             [Crate {
                 name: MOCK_CRATE_NAME.to_string(),
@@ -222,7 +940,7 @@ mod test {
         ];
         let mock_pico_args = pico_args::Arguments::from_vec(mock_cli_args);
 
-        let crates = crate_names_from_positional_args(mock_pico_args).unwrap();
+        let (_, crates) = subcommand_and_crate_names_from_positional_args(mock_pico_args).unwrap();
 
         assert_eq!(
             [
@@ -0,0 +1,88 @@
+//! Trusting extra CA certificates from `--ca-cert`, on top of the system ones.
+//!
+//! curl's `--cacert`, `$CARGO_HTTP_CAINFO` and `$SSL_CERT_FILE` all replace the
+//! system certificates rather than adding to them, so we give them a bundle with
+//! both.
+
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use home::cargo_home;
+use tempfile::NamedTempFile;
+
+use crate::{settings::settings, status};
+
+/// Where we keep the combined bundle. We rewrite it on every run that uses
+/// `--ca-cert`, so that it picks up changes to either half.
+const CA_BUNDLE: &str = ".quickinstall-ca-bundle.pem";
+
+/// Where Linux distributions and macOS keep their CA bundle, in the order that
+/// curl looks for them.
+const SYSTEM_CA_BUNDLES: &[&str] = &[
+    // Debian, Ubuntu, Arch, Alpine
+    "/etc/ssl/certs/ca-certificates.crt",
+    // Fedora, RHEL, CentOS
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    // openSUSE
+    "/etc/ssl/ca-bundle.pem",
+    // macOS, BSDs
+    "/etc/ssl/cert.pem",
+];
+
+/// The CA bundle to pass on to curl, cargo and cargo-binstall, if `--ca-cert`
+/// was given.
+///
+/// If we can't find the system bundle, or can't write the combined one, this
+/// is just the file from `--ca-cert`.
+pub(crate) fn ca_bundle() -> Option<&'static Path> {
+    static CA_BUNDLE_PATH: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+    CA_BUNDLE_PATH
+        .get_or_init(|| {
+            let ca_cert = settings().ca_cert.as_ref()?;
+            let bundle = system_ca_bundle().and_then(|system_bundle| {
+                write_combined_bundle(&system_bundle, ca_cert)
+                    .map_err(|err| {
+                        status!(
+                            "Failed to add the system CA certificates to {}, only trusting it: {err}",
+                            ca_cert.display()
+                        )
+                    })
+                    .ok()
+            });
+            Some(bundle.unwrap_or_else(|| ca_cert.clone()))
+        })
+        .as_deref()
+}
+
+fn system_ca_bundle() -> Option<PathBuf> {
+    // Respect the variables that curl and openssl use to move the bundle.
+    ["CURL_CA_BUNDLE", "SSL_CERT_FILE"]
+        .iter()
+        .filter_map(env::var_os)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .chain(SYSTEM_CA_BUNDLES.iter().map(PathBuf::from))
+        .find(|path| path.is_file())
+}
+
+fn write_combined_bundle(system_bundle: &Path, ca_cert: &Path) -> io::Result<PathBuf> {
+    let cargo_home = cargo_home()?;
+    let path = cargo_home.join(CA_BUNDLE);
+
+    let mut file = NamedTempFile::new_in(&cargo_home)?;
+    for bundle in [system_bundle, ca_cert].iter() {
+        let mut pem = fs::read(bundle)?;
+        if !pem.ends_with(b"\n") {
+            pem.push(b'\n');
+        }
+        file.write_all(&pem)?;
+    }
+    file.persist(&path).map_err(|err| err.error)?;
+
+    Ok(path)
+}
@@ -4,7 +4,7 @@ use std::{
     process::{self, Child, Command, Output},
 };
 
-use crate::{utf8_to_string_lossy, CommandFailed, InstallError};
+use crate::{utf8_to_string_lossy, very_verbose, CommandFailed, InstallError};
 
 pub trait CommandExt {
     fn formattable(&self) -> CommandFormattable<'_>;
@@ -20,12 +20,14 @@ impl CommandExt for Command {
     }
 
     fn output_checked_status(&mut self) -> Result<Output, InstallError> {
+        very_verbose!("Running {}", self.formattable());
         self.output()
             .map_err(InstallError::from)
             .and_then(|output| check_status(self, output))
     }
 
     fn spawn_with_cmd(mut self) -> Result<ChildWithCommand, InstallError> {
+        very_verbose!("Running {}", self.formattable());
         self.spawn()
             .map_err(InstallError::from)
             .map(move |child| ChildWithCommand { child, cmd: self })
@@ -90,6 +92,7 @@ fn check_status(cmd: &Command, output: Output) -> Result<Output, InstallError> {
     } else {
         Err(CommandFailed {
             command: cmd.formattable().to_string(),
+            code: output.status.code(),
             stdout: utf8_to_string_lossy(output.stdout),
             stderr: utf8_to_string_lossy(output.stderr),
         }
@@ -0,0 +1,239 @@
+//! Defaults for the command line options, read from `$CARGO_HOME/quickinstall.toml`,
+//! or from the file named by `$CARGO_QUICKINSTALL_CONFIG`.
+//!
+//! To avoid pulling in a toml parser, only a small subset of toml is supported:
+//! one `key = value` per line, where the value is a string, a boolean or an integer.
+
+use std::{convert::TryFrom, fmt, fs, io, path::PathBuf};
+
+const CONFIG_PATH_ENV: &str = "CARGO_QUICKINSTALL_CONFIG";
+
+#[derive(Debug, Default)]
+pub struct Config {
+    /// The file that the config was read from, if there was one.
+    pub path: Option<PathBuf>,
+    pub target: Option<String>,
+    pub fallback: Option<bool>,
+    pub binstall: Option<bool>,
+    pub mirror: Option<String>,
+    pub telemetry: Option<bool>,
+    pub stats_server: Option<String>,
+    pub jobs: Option<usize>,
+    pub retries: Option<usize>,
+    pub github_token: Option<String>,
+    pub proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    pub ca_cert: Option<PathBuf>,
+    pub connect_timeout: Option<u64>,
+    pub timeout: Option<u64>,
+    pub max_download_size: Option<u64>,
+    pub quiet: Option<bool>,
+}
+
+/// Where the effective value of a setting came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    ConfigFile,
+    EnvVar(&'static str),
+    CommandLine,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => f.write_str("default"),
+            Source::ConfigFile => f.write_str("config file"),
+            Source::EnvVar(name) => write!(f, "${name}"),
+            Source::CommandLine => f.write_str("command line"),
+        }
+    }
+}
+
+enum Value {
+    String(String),
+    Bool(bool),
+    Integer(u64),
+}
+
+impl Value {
+    /// Parse a value, followed by an optional `# comment`.
+    fn parse(s: &str) -> Option<Self> {
+        if let Some(s) = s.strip_prefix('"') {
+            // We don't support escapes, so the string ends at the next quote.
+            let (string, rest) = s.split_once('"')?;
+            let rest = rest.trim_start();
+            if string.contains('\\') || !(rest.is_empty() || rest.starts_with('#')) {
+                return None;
+            }
+            return Some(Value::String(string.to_string()));
+        }
+
+        let s = s.split_once('#').map_or(s, |(s, _comment)| s).trim_end();
+        match s {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => s.parse().ok().map(Value::Integer),
+        }
+    }
+
+    fn into_string(self) -> Result<String, &'static str> {
+        match self {
+            Value::String(s) => Ok(s),
+            _ => Err("expected a string"),
+        }
+    }
+
+    fn into_bool(self) -> Result<bool, &'static str> {
+        match self {
+            Value::Bool(b) => Ok(b),
+            _ => Err("expected `true` or `false`"),
+        }
+    }
+
+    fn into_u64(self) -> Result<u64, &'static str> {
+        match self {
+            Value::Integer(n) => Ok(n),
+            _ => Err("expected an integer"),
+        }
+    }
+
+    fn into_usize(self) -> Result<usize, &'static str> {
+        usize::try_from(self.into_u64()?).map_err(|_| "integer is too big")
+    }
+}
+
+impl Config {
+    /// Load the config file, if there is one.
+    ///
+    /// It is an error for `$CARGO_QUICKINSTALL_CONFIG` to point at a file that
+    /// doesn't exist, but `$CARGO_HOME/quickinstall.toml` is optional.
+    pub fn load() -> Result<Self, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let (path, required) = match std::env::var_os(CONFIG_PATH_ENV) {
+            Some(path) => (PathBuf::from(path), true),
+            None => (home::cargo_home()?.join("quickinstall.toml"), false),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => Ok(Self::parse(&contents, path)?),
+            Err(err) if !required && err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(format!("Failed to read config file {}: {err}", path.display()).into()),
+        }
+    }
+
+    fn parse(contents: &str, path: PathBuf) -> Result<Self, String> {
+        let mut config = Config::default();
+
+        for (line_no, line) in contents.lines().enumerate() {
+            let error = |msg: &str| format!("{}:{}: {msg}", path.display(), line_no + 1);
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected `key = value`"))?;
+            let key = key.trim();
+            let value = Value::parse(value.trim())
+                .ok_or_else(|| error("expected a string, boolean or integer"))?;
+
+            match key {
+                "target" => config.target = Some(value.into_string().map_err(error)?),
+                "fallback" => config.fallback = Some(value.into_bool().map_err(error)?),
+                "binstall" => config.binstall = Some(value.into_bool().map_err(error)?),
+                "mirror" => config.mirror = Some(value.into_string().map_err(error)?),
+                "telemetry" => config.telemetry = Some(value.into_bool().map_err(error)?),
+                "stats_server" => config.stats_server = Some(value.into_string().map_err(error)?),
+                "jobs" => config.jobs = Some(value.into_usize().map_err(error)?),
+                "retries" => config.retries = Some(value.into_usize().map_err(error)?),
+                "github_token" => config.github_token = Some(value.into_string().map_err(error)?),
+                "proxy" => config.proxy = Some(value.into_string().map_err(error)?),
+                "no_proxy" => config.no_proxy = Some(value.into_string().map_err(error)?),
+                "ca_cert" => config.ca_cert = Some(value.into_string().map_err(error)?.into()),
+                "connect_timeout" => {
+                    config.connect_timeout = Some(value.into_u64().map_err(error)?)
+                }
+                "timeout" => config.timeout = Some(value.into_u64().map_err(error)?),
+                "max_download_size" => {
+                    config.max_download_size = Some(value.into_u64().map_err(error)?)
+                }
+                "quiet" => config.quiet = Some(value.into_bool().map_err(error)?),
+                _ => return Err(error(&format!("unknown key `{key}`"))),
+            }
+        }
+
+        config.path = Some(path);
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse(
+            r#"
+            # Our CI machines
+            target = "x86_64-unknown-linux-musl"
+            fallback = false # like --no-fallback
+            binstall=false
+            mirror = "https://example.com/#fragment" # not a comment
+            jobs = 4
+            retries = 0
+            proxy = "http://proxy.example.com:3128"
+            ca_cert = "/etc/ssl/certs/corp.pem"
+            connect_timeout = 10
+            max_download_size = 5000000000
+            quiet = true
+            "#,
+            PathBuf::from("quickinstall.toml"),
+        )
+        .unwrap();
+
+        assert_eq!(config.target.as_deref(), Some("x86_64-unknown-linux-musl"));
+        assert_eq!(config.fallback, Some(false));
+        assert_eq!(config.binstall, Some(false));
+        assert_eq!(
+            config.mirror.as_deref(),
+            Some("https://example.com/#fragment")
+        );
+        assert_eq!(config.jobs, Some(4));
+        assert_eq!(config.retries, Some(0));
+        assert_eq!(
+            config.proxy.as_deref(),
+            Some("http://proxy.example.com:3128")
+        );
+        assert_eq!(
+            config.ca_cert.as_deref(),
+            Some(std::path::Path::new("/etc/ssl/certs/corp.pem"))
+        );
+        assert_eq!(config.connect_timeout, Some(10));
+        // Bigger than a 32-bit usize.
+        assert_eq!(config.max_download_size, Some(5_000_000_000));
+        assert_eq!(config.quiet, Some(true));
+    }
+
+    #[test]
+    fn test_parse_config_err() {
+        for contents in [
+            "target = x86_64-unknown-linux-musl",
+            "fallback = \"no\"",
+            "mirror = \"https://example.com\" trailing",
+            "no-such-key = true",
+            "[table]",
+        ] {
+            let result = Config::parse(contents, PathBuf::from("quickinstall.toml"));
+            assert!(result.is_err(), "{:?}", contents);
+        }
+
+        assert_eq!(
+            Config::parse("\njobs = true", PathBuf::from("quickinstall.toml")).unwrap_err(),
+            "quickinstall.toml:2: expected an integer"
+        );
+    }
+}
@@ -1,5 +1,8 @@
 use crate::{CommandFailed, CrateDetails, JsonExtError};
-use std::fmt::{Debug, Display};
+use std::{
+    fmt::{Debug, Display},
+    time::Duration,
+};
 
 use tinyjson::JsonParseError;
 
@@ -8,29 +11,154 @@ pub enum InstallError {
     CommandFailed(CommandFailed),
     IoError(std::io::Error),
     CargoInstallFailed,
-    CrateDoesNotExist { crate_name: String },
+    CrateDoesNotExist {
+        crate_name: String,
+    },
+    /// Asking crates.io for the versions of a crate failed, for a reason other than
+    /// the crate not existing.
+    VersionLookupFailed {
+        crate_name: String,
+        err: Box<InstallError>,
+    },
+    /// Neither `rustc -vV` nor guessing could tell us which target we are on.
+    TargetDetectionFailed(Box<InstallError>),
+    /// The server responded to `url` with an HTTP error `status`.
+    Http {
+        status: u16,
+        url: String,
+        /// How long the server asked us to wait before trying again, from its
+        /// `Retry-After` header.
+        retry_after: Option<Duration>,
+    },
+    /// curl failed to download `url` without getting an HTTP error, e.g. because it
+    /// couldn't connect.
+    DownloadFailed {
+        url: String,
+        err: CommandFailed,
+    },
+    /// tar or unzip failed to list or extract a package.
+    ExtractFailed(CommandFailed),
     NoFallback(CrateDetails),
-    InvalidJson { url: String, err: JsonParseError },
+    InvalidJson {
+        url: String,
+        err: JsonParseError,
+    },
     JsonErr(JsonExtError),
-    FailToParseRustcOutput { reason: &'static str },
+    FailToParseRustcOutput {
+        reason: &'static str,
+    },
+    BinaryCollision {
+        binary: String,
+        /// `None` if the file isn't in cargo's or our install records.
+        installed_by: Option<String>,
+    },
 }
 
 impl InstallError {
+    /// The HTTP status that the server responded with, if this is an HTTP error.
+    pub fn http_status(&self) -> Option<u16> {
+        match self {
+            Self::Http { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.http_status() == Some(404)
+    }
+
+    /// curl failed to download a package.
+    #[deprecated(note = "use `is_not_found()` instead")]
     pub fn is_curl_404(&self) -> bool {
-        matches!(
-            self,
-            Self::CommandFailed(CommandFailed { stderr, .. })
-            if stderr.contains("The requested URL returned error: 404")
-        )
+        self.is_not_found()
+    }
+
+    /// Turn a failed curl command for `url` into [`InstallError::Http`] if curl
+    /// reported an HTTP error status, or [`InstallError::DownloadFailed`] otherwise.
+    ///
+    /// The status comes from [`CURL_WRITE_OUT_HTTP_STATUS`] if the command was run
+    /// with it, or else from curl's error message.
+    pub(crate) fn into_download_failed(self, url: &str) -> Self {
+        match self {
+            Self::CommandFailed(mut err) => match take_http_status(&mut err.stderr)
+                .or_else(|| http_status_from_curl_error(&err))
+            {
+                Some((status, retry_after)) if status >= 400 => Self::Http {
+                    status,
+                    url: url.to_string(),
+                    retry_after,
+                },
+                _ => Self::DownloadFailed {
+                    url: url.to_string(),
+                    err,
+                },
+            },
+            err => err,
+        }
+    }
+
+    /// Turn a failed tar or unzip command into [`InstallError::ExtractFailed`].
+    pub(crate) fn into_extract_failed(self) -> Self {
+        match self {
+            Self::CommandFailed(err) => Self::ExtractFailed(err),
+            err => err,
+        }
     }
 }
 
+const HTTP_STATUS_PREFIX: &str = "cargo-quickinstall-http-status: ";
+
+/// Pass this to curl's `--write-out` to have it print the HTTP status and
+/// `Retry-After` header of the last response to stderr, where
+/// [`InstallError::into_download_failed`] can find them.
+///
+/// curl prints `000` if it never got a response. This needs curl 7.84 for
+/// `%header{}`.
+pub(crate) const CURL_WRITE_OUT_HTTP_STATUS: &str =
+    "%{stderr}cargo-quickinstall-http-status: %{http_code} %header{retry-after}\\n";
+
+/// [`CURL_WRITE_OUT_HTTP_STATUS`] without the `Retry-After` header, for curl 7.63
+/// to 7.83. Older versions can't write to stderr at all.
+pub(crate) const CURL_WRITE_OUT_HTTP_STATUS_ONLY: &str =
+    "%{stderr}cargo-quickinstall-http-status: %{http_code}\\n";
+
+/// Remove the line that [`CURL_WRITE_OUT_HTTP_STATUS`] added to `stderr`, and
+/// return the status and `Retry-After` in it.
+///
+/// We only understand `Retry-After` in seconds, not as a date.
+fn take_http_status(stderr: &mut String) -> Option<(u16, Option<Duration>)> {
+    let start = stderr.rfind(HTTP_STATUS_PREFIX)?;
+    let mut fields = stderr[start + HTTP_STATUS_PREFIX.len()..].split_whitespace();
+    let status = fields.next()?.parse().ok()?;
+    let retry_after = fields
+        .next()
+        .and_then(|secs| secs.parse().ok())
+        .map(Duration::from_secs);
+    stderr.truncate(start);
+    Some((status, retry_after))
+}
+
+/// Find the HTTP status in curl's `--fail` error message, for curl that is too
+/// old for [`CURL_WRITE_OUT_HTTP_STATUS`]. Depending on the version, this looks like
+/// `The requested URL returned error: 404` or `... error: 404 Not Found`.
+fn http_status_from_curl_error(err: &CommandFailed) -> Option<(u16, Option<Duration>)> {
+    if err.code != Some(22) {
+        return None;
+    }
+    let (_, rest) = err
+        .stderr
+        .split_once("The requested URL returned error: ")?;
+    let status = rest.get(..3)?.parse().ok()?;
+    Some((status, None))
+}
+
 impl std::error::Error for InstallError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             Self::IoError(io_err) => Some(io_err),
             Self::InvalidJson { err, .. } => Some(err),
             Self::JsonErr(err) => Some(err),
+            Self::VersionLookupFailed { err, .. } | Self::TargetDetectionFailed(err) => Some(err),
             _ => None,
         }
     }
@@ -50,21 +178,7 @@ impl Display for InstallError {
             &InstallError::MissingCrateNameArgument(usage_text) => {
                 write!(f, "No crate name specified.\n\n{usage_text}")
             }
-            InstallError::CommandFailed(CommandFailed {
-                command,
-                stdout,
-                stderr,
-            }) => {
-                write!(f, "Command failed:\n    {command}\n")?;
-                if !stdout.is_empty() {
-                    write!(f, "Stdout:\n{stdout}\n")?;
-                }
-                if !stderr.is_empty() {
-                    write!(f, "Stderr:\n{stderr}")?;
-                }
-
-                Ok(())
-            }
+            InstallError::CommandFailed(err) => Display::fmt(err, f),
             InstallError::IoError(e) => Display::fmt(e, f),
             InstallError::CargoInstallFailed => {
                 f.write_str("`cargo install` didn't work either. Looks like you're on your own.")
@@ -73,6 +187,41 @@ impl Display for InstallError {
             InstallError::CrateDoesNotExist { crate_name } => {
                 write!(f, "`{crate_name}` does not exist on crates.io.")
             }
+            InstallError::VersionLookupFailed { crate_name, err } => {
+                write!(
+                    f,
+                    "Failed to look up the versions of `{crate_name}` on crates.io: {err}"
+                )
+            }
+            InstallError::TargetDetectionFailed(err) => {
+                write!(
+                    f,
+                    "Failed to detect the target triple, use `--target` to set it: {err}"
+                )
+            }
+            InstallError::Http {
+                status: status @ (403 | 429),
+                url,
+                ..
+            } => {
+                write!(
+                    f,
+                    "Failed to download {url}: the server responded with HTTP {status}, \
+                     we are probably being rate limited, please try again later"
+                )
+            }
+            InstallError::Http { status, url, .. } => {
+                write!(
+                    f,
+                    "Failed to download {url}: the server responded with HTTP {status}"
+                )
+            }
+            InstallError::DownloadFailed { url, err } => {
+                write!(f, "Failed to download {url}\n{err}")
+            }
+            InstallError::ExtractFailed(err) => {
+                write!(f, "Failed to extract the package\n{err}")
+            }
             InstallError::NoFallback(crate_details) => {
                 write!(
                     f,
@@ -87,7 +236,42 @@ impl Display for InstallError {
             InstallError::FailToParseRustcOutput { reason } => {
                 write!(f, "Failed to parse `rustc -vV` output: {reason}")
             }
+            InstallError::BinaryCollision {
+                binary,
+                installed_by: Some(installed_by),
+            } => write!(
+                f,
+                "`{binary}` is already installed by `{installed_by}`. Use `--force` to overwrite it."
+            ),
+            InstallError::BinaryCollision {
+                binary,
+                installed_by: None,
+            } => write!(
+                f,
+                "`{binary}` already exists, and we don't know which crate installed it. Use `--force` to overwrite it."
+            ),
+        }
+    }
+}
+
+impl Display for CommandFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let CommandFailed {
+            command,
+            stdout,
+            stderr,
+            ..
+        } = self;
+
+        write!(f, "Command failed:\n    {command}\n")?;
+        if !stdout.is_empty() {
+            write!(f, "Stdout:\n{stdout}\n")?;
         }
+        if !stderr.is_empty() {
+            write!(f, "Stderr:\n{stderr}")?;
+        }
+
+        Ok(())
     }
 }
 
@@ -112,6 +296,7 @@ impl From<JsonExtError> for InstallError {
 pub enum InstallSuccess {
     InstalledFromTarball,
     BuiltFromSource,
+    AlreadyInstalled,
 }
 
 /**
@@ -130,14 +315,68 @@ pub fn install_result_to_status_str(result: &Result<InstallSuccess, InstallError
     match result {
         Ok(InstallSuccess::InstalledFromTarball) => "installed-from-tarball",
         Ok(InstallSuccess::BuiltFromSource) => "built-from-source",
+        Ok(InstallSuccess::AlreadyInstalled) => "already-installed",
         Err(InstallError::CargoInstallFailed) => "cargo-install-failed",
         Err(InstallError::NoFallback(_)) => "no-fallback",
-        Err(InstallError::MissingCrateNameArgument(_))
-        | Err(InstallError::CommandFailed(_))
-        | Err(InstallError::IoError(_))
-        | Err(InstallError::CrateDoesNotExist { .. })
-        | Err(InstallError::InvalidJson { .. })
-        | Err(InstallError::JsonErr(_))
-        | Err(InstallError::FailToParseRustcOutput { .. }) => "other-error",
+        Err(InstallError::CrateDoesNotExist { .. }) => "crate-not-found",
+        Err(InstallError::VersionLookupFailed { .. }) => "version-lookup-failed",
+        Err(InstallError::TargetDetectionFailed(_))
+        | Err(InstallError::FailToParseRustcOutput { .. }) => "target-detection-failed",
+        Err(InstallError::Http { .. }) | Err(InstallError::DownloadFailed { .. }) => {
+            "download-failed"
+        }
+        Err(InstallError::ExtractFailed(_)) => "extract-failed",
+        Err(InstallError::BinaryCollision { .. }) => "binary-collision",
+        Err(InstallError::InvalidJson { .. }) | Err(InstallError::JsonErr(_)) => "invalid-json",
+        Err(InstallError::IoError(_)) => "io-error",
+        Err(InstallError::MissingCrateNameArgument(_)) | Err(InstallError::CommandFailed(_)) => {
+            "other-error"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curl_failed(stderr: &str) -> InstallError {
+        InstallError::CommandFailed(CommandFailed {
+            command: "curl https://example.com".to_string(),
+            code: Some(22),
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_into_download_failed() {
+        let err = curl_failed(
+            "curl: (22) The requested URL returned error: 404\n\
+             cargo-quickinstall-http-status: 404\n",
+        )
+        .into_download_failed("https://example.com");
+        assert!(err.is_not_found());
+
+        let err = curl_failed("cargo-quickinstall-http-status: 503\n")
+            .into_download_failed("https://example.com");
+        assert_eq!(err.http_status(), Some(503));
+
+        let err = curl_failed(
+            "curl: (7) Failed to connect to example.com port 443\n\
+             cargo-quickinstall-http-status: 000\n",
+        )
+        .into_download_failed("https://example.com");
+        match err {
+            InstallError::DownloadFailed { err, .. } => assert_eq!(
+                err.stderr,
+                "curl: (7) Failed to connect to example.com port 443\n"
+            ),
+            err => panic!("unexpected error: {}", err),
+        }
+
+        // curl older than 7.63, without `--write-out`.
+        let err = curl_failed("curl: (22) The requested URL returned error: 404 Not Found\n")
+            .into_download_failed("https://example.com");
+        assert!(err.is_not_found());
     }
 }
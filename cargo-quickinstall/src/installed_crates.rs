@@ -0,0 +1,204 @@
+use home::cargo_home;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::Path,
+};
+use tempfile::NamedTempFile;
+use tinyjson::JsonValue;
+
+use crate::{get_cargo_bin_dir, InstallError, JsonValueExt};
+
+/// Where we record what `cargo quickinstall --no-binstall` installed, since cargo
+/// doesn't know about it. It has the same layout as `.crates2.json`, but only
+/// with the `bins` of each install.
+const QUICKINSTALL_RECORD: &str = ".quickinstall.json";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledCrate {
+    pub name: String,
+    pub version: String,
+}
+
+/// Returns which installed crate each binary in `$CARGO_HOME/bin` belongs to,
+/// according to the metadata that `cargo install` and `cargo binstall` keep in
+/// `$CARGO_HOME/.crates2.json`, and the record of what `cargo quickinstall
+/// --no-binstall` installed.
+///
+/// If both claim a binary, the latter wins, since cargo doesn't know when we
+/// replace one of its binaries.
+pub fn get_installed_binaries() -> Result<BTreeMap<String, InstalledCrate>, InstallError> {
+    let cargo_home = cargo_home()?;
+
+    let mut binaries = read_installs(&cargo_home.join(".crates2.json"))?;
+    binaries.append(&mut read_installs(&cargo_home.join(QUICKINSTALL_RECORD))?);
+
+    Ok(binaries)
+}
+
+/// Returns whether `version` of `crate_name` is already installed, i.e. all the
+/// binaries recorded for it are still in the cargo bin dir.
+pub fn is_crate_version_installed(crate_name: &str, version: &str) -> Result<bool, InstallError> {
+    let bin_dir = get_cargo_bin_dir()?;
+
+    let mut binaries = get_installed_binaries()?
+        .into_iter()
+        .filter(|(_, installed_crate)| installed_crate.name == crate_name)
+        .peekable();
+    if binaries.peek().is_none() {
+        return Ok(false);
+    }
+
+    Ok(binaries.all(|(binary, installed_crate)| {
+        installed_crate.version == version && bin_dir.join(binary).is_file()
+    }))
+}
+
+/// Record that `version` of `crate_name` installed `binaries`, replacing whatever
+/// we recorded for an earlier version of it, or for other crates that used to
+/// own those binaries.
+pub(crate) fn record_installed_binaries(
+    crate_name: &str,
+    version: &str,
+    binaries: &[String],
+) -> Result<(), InstallError> {
+    let cargo_home = cargo_home()?;
+    let path = cargo_home.join(QUICKINSTALL_RECORD);
+
+    let mut installed = read_installs(&path)?;
+    installed.retain(|binary, installed_crate| {
+        installed_crate.name != crate_name && !binaries.contains(binary)
+    });
+    installed.extend(binaries.iter().map(|binary| {
+        (
+            binary.clone(),
+            InstalledCrate {
+                name: crate_name.to_string(),
+                version: version.to_string(),
+            },
+        )
+    }));
+
+    // Write the whole file at once, so that an interrupted install can't leave
+    // it half-written.
+    let mut file = NamedTempFile::new_in(&cargo_home)?;
+    io::Write::write_all(&mut file, installs_to_json(&installed).as_bytes())?;
+    file.persist(&path).map_err(|err| err.error)?;
+
+    Ok(())
+}
+
+fn read_installs(path: &Path) -> Result<BTreeMap<String, InstalledCrate>, InstallError> {
+    match std::fs::read_to_string(path) {
+        Ok(json) => parse_crates2_json(&json, &path.display().to_string()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// The inverse of [`parse_crates2_json`].
+fn installs_to_json(binaries: &BTreeMap<String, InstalledCrate>) -> String {
+    let mut installs = BTreeMap::<String, Vec<JsonValue>>::new();
+    for (binary, installed_crate) in binaries {
+        installs
+            .entry(format!(
+                "{} {}",
+                installed_crate.name, installed_crate.version
+            ))
+            .or_default()
+            .push(JsonValue::String(binary.clone()));
+    }
+
+    let installs = installs
+        .into_iter()
+        .map(|(package_id, bins)| {
+            let install = HashMap::from([("bins".to_string(), JsonValue::Array(bins))]);
+            (package_id, JsonValue::Object(install))
+        })
+        .collect();
+    let json = HashMap::from([("installs".to_string(), JsonValue::Object(installs))]);
+
+    // Strings, arrays and objects can always be stringified.
+    JsonValue::Object(json).stringify().unwrap()
+}
+
+fn parse_crates2_json(
+    json: &str,
+    path: &str,
+) -> Result<BTreeMap<String, InstalledCrate>, InstallError> {
+    let installs = json
+        .parse::<JsonValue>()
+        .map_err(|err| InstallError::InvalidJson {
+            url: path.to_string(),
+            err,
+        })?
+        .get_owned(&"installs")?;
+
+    let JsonValue::Object(installs) = installs else {
+        return Ok(BTreeMap::new());
+    };
+
+    let mut binaries = BTreeMap::new();
+    for (package_id, install) in installs {
+        // Package ids look like "ripgrep 14.1.1 (registry+https://github.com/rust-lang/crates.io-index)"
+        let mut parts = package_id.splitn(3, ' ');
+        let (Some(name), Some(version)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let installed_crate = InstalledCrate {
+            name: name.to_string(),
+            version: version.to_string(),
+        };
+
+        for bin in install.get_owned(&"bins")?.try_into_vec()? {
+            binaries.insert(bin.try_into_string()?, installed_crate.clone());
+        }
+    }
+
+    Ok(binaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_crates2_json() {
+        let json = r#"{"installs":{
+            "ripgrep 14.1.1 (registry+https://github.com/rust-lang/crates.io-index)":{"bins":["rg"]},
+            "cargo-update 18.0.0 (registry+https://github.com/rust-lang/crates.io-index)":{"bins":["cargo-install-update","cargo-install-update-config"]}
+        }}"#;
+
+        let binaries = parse_crates2_json(json, ".crates2.json").unwrap();
+
+        assert_eq!(binaries.len(), 3);
+        assert_eq!(
+            binaries["rg"],
+            InstalledCrate {
+                name: "ripgrep".to_string(),
+                version: "14.1.1".to_string(),
+            }
+        );
+        assert_eq!(binaries["cargo-install-update-config"].name, "cargo-update");
+    }
+
+    #[test]
+    fn test_installs_to_json() {
+        let binaries = [("rg", "ripgrep"), ("cargo-install-update", "cargo-update")]
+            .iter()
+            .map(|(binary, name)| {
+                let installed_crate = InstalledCrate {
+                    name: name.to_string(),
+                    version: "1.0.0".to_string(),
+                };
+                (binary.to_string(), installed_crate)
+            })
+            .collect();
+
+        let json = installs_to_json(&binaries);
+        assert_eq!(
+            parse_crates2_json(&json, QUICKINSTALL_RECORD).unwrap(),
+            binaries
+        );
+    }
+}
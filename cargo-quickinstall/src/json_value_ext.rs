@@ -31,6 +31,10 @@ pub trait JsonValueExt {
 
     fn try_into_string(self) -> Result<String, JsonExtError>;
 
+    fn try_into_vec(self) -> Result<Vec<JsonValue>, JsonExtError>;
+
+    fn try_into_bool(self) -> Result<bool, JsonExtError>;
+
     fn get_value_type(&self) -> &'static str;
 }
 
@@ -46,6 +50,20 @@ impl JsonValueExt for JsonValue {
         }
     }
 
+    fn try_into_vec(self) -> Result<Vec<JsonValue>, JsonExtError> {
+        match self {
+            JsonValue::Array(values) => Ok(values),
+            value => Err(JsonExtError::unexpected(&value, "Array")),
+        }
+    }
+
+    fn try_into_bool(self) -> Result<bool, JsonExtError> {
+        match self {
+            JsonValue::Boolean(b) => Ok(b),
+            value => Err(JsonExtError::unexpected(&value, "Boolean")),
+        }
+    }
+
     fn get_value_type(&self) -> &'static str {
         match self {
             JsonValue::Number(..) => "Number",
@@ -4,7 +4,13 @@
 //! `cargo install` otherwise.
 
 use guess_host_triple::guess_host_triple;
-use std::{fs::File, path::Path, process};
+use std::{
+    fs::{self, File},
+    io::{Seek, Write},
+    path::Path,
+    process,
+    sync::OnceLock,
+};
 use tempfile::NamedTempFile;
 use tinyjson::JsonValue;
 
@@ -12,6 +18,9 @@ pub mod install_error;
 
 use install_error::*;
 
+mod ca_bundle;
+use ca_bundle::ca_bundle;
+
 mod command_ext;
 pub use command_ext::{ChildWithCommand, CommandExt, CommandFormattable};
 
@@ -19,11 +28,30 @@ mod json_value_ext;
 pub use json_value_ext::{JsonExtError, JsonKey, JsonValueExt};
 
 mod utils;
-pub use utils::{get_cargo_bin_dir, utf8_to_string_lossy};
+pub use utils::{format_size, get_cargo_bin_dir, utf8_to_string_lossy};
+
+mod output;
+pub use output::{timed, verbosity, Verbosity};
+
+mod progress;
+use progress::Progress;
+
+mod retry;
+use retry::with_retries;
+
+mod settings;
+use settings::settings;
+pub use settings::{init_settings, Settings, DEFAULT_RETRIES};
+
+mod installed_crates;
+use installed_crates::record_installed_binaries;
+pub use installed_crates::{get_installed_binaries, is_crate_version_installed, InstalledCrate};
 
 #[derive(Debug)]
 pub struct CommandFailed {
     pub command: String,
+    /// The exit code, or `None` if the command was killed by a signal.
+    pub code: Option<i32>,
     pub stdout: String,
     pub stderr: String,
 }
@@ -35,6 +63,22 @@ pub struct CrateDetails {
     pub target: String,
 }
 
+/// The targets that we build packages for.
+///
+/// This must be kept in sync with the `supported-targets` file at the root of the repo.
+pub const SUPPORTED_TARGETS: &[&str] = &[
+    "x86_64-pc-windows-msvc",
+    "x86_64-apple-darwin",
+    "aarch64-apple-darwin",
+    "x86_64-unknown-linux-gnu",
+    "x86_64-unknown-linux-musl",
+    "aarch64-unknown-linux-gnu",
+    "aarch64-unknown-linux-musl",
+    "aarch64-pc-windows-msvc",
+    "armv7-unknown-linux-musleabihf",
+    "armv7-unknown-linux-gnueabihf",
+];
+
 /// Return (archive_format, url)
 fn get_binstall_upstream_url(target: &str) -> (&'static str, String) {
     let archive_format = if target.contains("linux") {
@@ -52,7 +96,11 @@ pub fn download_and_install_binstall_from_upstream(target: &str) -> Result<(), I
     let (archive_format, url) = get_binstall_upstream_url(target);
 
     if archive_format == "tgz" {
-        untar(curl(&url)?)?;
+        let (tarball, tarball_path) = NamedTempFile::new()?.into_parts();
+
+        timed("Downloading cargo-binstall", || curl_file(&url, tarball))?;
+
+        timed("Extracting cargo-binstall", || untar_file(&tarball_path))?;
 
         Ok(())
     } else {
@@ -60,9 +108,9 @@ pub fn download_and_install_binstall_from_upstream(target: &str) -> Result<(), I
 
         let (zip_file, zip_file_temp_path) = NamedTempFile::new()?.into_parts();
 
-        curl_file(&url, zip_file)?;
+        timed("Downloading cargo-binstall", || curl_file(&url, zip_file))?;
 
-        unzip(&zip_file_temp_path)?;
+        timed("Extracting cargo-binstall", || unzip(&zip_file_temp_path))?;
 
         Ok(())
     }
@@ -95,7 +143,8 @@ pub fn unzip(zip_file: &Path) -> Result<(), InstallError> {
         .arg(zip_file)
         .arg("-d")
         .arg(bin_dir)
-        .output_checked_status()?;
+        .output_checked_status()
+        .map_err(InstallError::into_extract_failed)?;
 
     Ok(())
 }
@@ -112,17 +161,54 @@ pub fn get_cargo_binstall_version() -> Option<String> {
     String::from_utf8(output.stdout).ok()
 }
 
+/// Whether the installed `cargo binstall` accepts `flag`, according to its `--help`.
+///
+/// We accept any version from 0.17 on, and many of its flags are much newer than that.
+pub fn cargo_binstall_supports(flag: &str) -> bool {
+    static HELP: OnceLock<Option<String>> = OnceLock::new();
+
+    HELP.get_or_init(|| {
+        let output = process::Command::new("cargo")
+            .args(["binstall", "--help"])
+            .output_checked_status()
+            .ok()?;
+        Some(utf8_to_string_lossy(output.stdout))
+    })
+    .as_deref()
+    .is_some_and(|help| help_mentions_flag(help, flag))
+}
+
+fn help_mentions_flag(help: &str, flag: &str) -> bool {
+    help.split(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '-'))
+        .any(|word| word == flag)
+}
+
+/// Install the pre-built package for `details`, falling back to `cargo install`
+/// if there isn't one and `fallback` is set.
+///
+/// Unless `force` is set, this does nothing if the same version is already
+/// installed, and refuses to overwrite binaries that belong to another crate.
 pub fn install_crate_curl(
     details: &CrateDetails,
     fallback: bool,
+    force: bool,
 ) -> Result<InstallSuccess, InstallError> {
+    if !force && is_crate_version_installed(&details.crate_name, &details.version)? {
+        status!(
+            "{crate_name}@{version} is already installed, use --force to reinstall it.",
+            crate_name = details.crate_name,
+            version = details.version,
+        );
+        return Ok(InstallSuccess::AlreadyInstalled);
+    }
+
     let urls = get_quickinstall_download_urls(details);
 
-    let res = match curl_and_untar(&urls[0]) {
-        Err(err) if err.is_curl_404() => {
-            println!("Fallback to old release schema");
+    let res = match download_and_untar(&urls[0], details, force) {
+        Err(err) if err.is_not_found() => {
+            status!("Fallback to old release schema");
 
-            curl_and_untar(&urls[1])
+            download_and_untar(&urls[1], details, force)
         }
         res => res,
     };
@@ -132,28 +218,31 @@ pub fn install_crate_curl(
             let bin_dir = get_cargo_bin_dir()?;
 
             // tar output contains its own newline.
-            print!(
-                "Installed {crate_name}@{version} to {bin_dir}:\n{tar_output}",
+            status!(
+                "Installed {crate_name}@{version} to {bin_dir}:\n{}",
+                tar_output.trim_end(),
                 crate_name = details.crate_name,
                 version = details.version,
                 bin_dir = bin_dir.display(),
             );
             Ok(InstallSuccess::InstalledFromTarball)
         }
-        Err(err) if err.is_curl_404() => {
+        Err(err) if err.is_not_found() => {
             if !fallback {
                 return Err(InstallError::NoFallback(details.clone()));
             }
 
-            println!(
+            status!(
                 "Could not find a pre-built package for {} {} on {}.",
-                details.crate_name, details.version, details.target
+                details.crate_name,
+                details.version,
+                details.target
             );
-            println!("We have reported your installation request, so it should be built soon.");
+            status!("Falling back to `cargo install`.");
 
-            println!("Falling back to `cargo install`.");
-
-            let status = prepare_cargo_install_cmd(details).status()?;
+            let mut cmd = prepare_cargo_install_cmd(details);
+            very_verbose!("Running {}", cmd.formattable());
+            let status = timed("cargo install", || cmd.status())?;
 
             if status.success() {
                 Ok(InstallSuccess::BuiltFromSource)
@@ -165,39 +254,125 @@ pub fn install_crate_curl(
     }
 }
 
-fn curl_and_untar(url: &str) -> Result<String, InstallError> {
-    untar(curl(url)?)
+/// Download the tarball to a temporary file so that we can check what is in it
+/// before extracting it into the cargo bin dir, and record what we extracted.
+fn download_and_untar(
+    url: &str,
+    details: &CrateDetails,
+    force: bool,
+) -> Result<String, InstallError> {
+    let (tarball, tarball_path) = NamedTempFile::new()?.into_parts();
+
+    timed("Downloading", || curl_file(url, tarball))?;
+
+    let binaries = list_tar_file(&tarball_path)?;
+    if !force {
+        check_for_binary_collisions(&binaries, &details.crate_name)?;
+    }
+
+    let tar_output = timed("Extracting", || untar_file(&tarball_path))?;
+
+    record_installed_binaries(&details.crate_name, &details.version, &binaries)?;
+
+    Ok(tar_output)
+}
+
+/// Error out if extracting `binaries` would overwrite a file in the cargo bin dir
+/// that isn't recorded as belonging to `crate_name`, either because another crate
+/// installed it or because we don't know where it came from.
+fn check_for_binary_collisions(binaries: &[String], crate_name: &str) -> Result<(), InstallError> {
+    let bin_dir = get_cargo_bin_dir()?;
+    let installed_binaries = get_installed_binaries()?;
+
+    for binary in binaries {
+        if !bin_dir.join(binary).exists() {
+            continue;
+        }
+
+        let installed_by = installed_binaries
+            .get(binary)
+            .map(|installed_crate| installed_crate.name.as_str());
+        if installed_by != Some(crate_name) {
+            return Err(InstallError::BinaryCollision {
+                binary: binary.clone(),
+                installed_by: installed_by.map(str::to_string),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Run a crates.io lookup for `crate_name`, turning its errors into
+/// [`InstallError::CrateDoesNotExist`] or [`InstallError::VersionLookupFailed`].
+fn lookup_versions<T>(
+    crate_name: &str,
+    lookup: impl FnOnce() -> Result<T, InstallError>,
+) -> Result<T, InstallError> {
+    lookup().map_err(|err| {
+        if err.is_not_found() {
+            InstallError::CrateDoesNotExist {
+                crate_name: crate_name.to_string(),
+            }
+        } else {
+            InstallError::VersionLookupFailed {
+                crate_name: crate_name.to_string(),
+                err: Box::new(err),
+            }
+        }
+    })
 }
 
 pub fn get_latest_version(crate_name: &str) -> Result<String, InstallError> {
     let url = format!("https://crates.io/api/v1/crates/{crate_name}");
 
-    curl_json(&url)
-        .map_err(|e| {
-            if e.is_curl_404() {
-                InstallError::CrateDoesNotExist {
-                    crate_name: crate_name.to_string(),
+    timed("Looking up the latest version", || {
+        lookup_versions(crate_name, || {
+            Ok(curl_json(&url)?
+                .get_owned(&"crate")?
+                .get_owned(&"max_stable_version")?
+                .try_into_string()?)
+        })
+    })
+}
+
+/// Returns up to `limit` of the most recent non-yanked versions of `crate_name`,
+/// newest first.
+pub fn get_recent_versions(crate_name: &str, limit: usize) -> Result<Vec<String>, InstallError> {
+    let url = format!("https://crates.io/api/v1/crates/{crate_name}/versions");
+
+    lookup_versions(crate_name, || {
+        let versions = curl_json(&url)?.get_owned(&"versions")?.try_into_vec()?;
+
+        let mut recent_versions = Vec::with_capacity(limit);
+        for version in versions {
+            if recent_versions.len() == limit {
+                break;
+            }
+            if let JsonValue::Object(mut version) = version {
+                if let Some(Ok(true)) = version.remove("yanked").map(JsonValueExt::try_into_bool) {
+                    continue;
+                }
+                if let Some(num) = version.remove("num") {
+                    recent_versions.push(num.try_into_string()?);
                 }
-            } else {
-                e
             }
-        })?
-        .get_owned(&"crate")?
-        .get_owned(&"max_stable_version")?
-        .try_into_string()
-        .map_err(From::from)
+        }
+
+        Ok(recent_versions)
+    })
 }
 
 pub fn get_target_triple() -> Result<String, InstallError> {
-    match get_target_triple_from_rustc() {
+    match timed("Detecting the target", get_target_triple_from_rustc) {
         Ok(target) => Ok(target),
         Err(err) => {
             if let Some(target) = guess_host_triple() {
-                println!("get_target_triple_from_rustc() failed due to {err}, fallback to guess_host_triple");
+                status!("get_target_triple_from_rustc() failed due to {err}, fallback to guess_host_triple");
                 Ok(target.to_string())
             } else {
-                println!("get_target_triple_from_rustc() failed due to {err}, fallback to guess_host_triple also failed");
-                Err(err)
+                status!("get_target_triple_from_rustc() failed due to {err}, fallback to guess_host_triple also failed");
+                Err(InstallError::TargetDetectionFailed(Box::new(err)))
             }
         }
     }
@@ -240,18 +415,66 @@ fn get_target_triple_from_rustc() -> Result<String, InstallError> {
     Ok(parts.join("-"))
 }
 
+const STATS_SERVER_URL: &str = "https://cargo-quickinstall-stats-server.fly.dev";
+
+fn get_stats_url(path: &str) -> String {
+    let base_url = settings()
+        .stats_server
+        .as_deref()
+        .unwrap_or(STATS_SERVER_URL)
+        .trim_end_matches('/');
+
+    format!("{base_url}{path}")
+}
+
+/// Returns everything that [`report_stats_in_background`] sends to our stats server
+/// about an install, as (key, value) query parameters.
+pub fn stats_query_params(
+    details: &CrateDetails,
+    result: &Result<InstallSuccess, InstallError>,
+) -> [(&'static str, String); 5] {
+    [
+        ("crate", details.crate_name.clone()),
+        ("version", details.version.clone()),
+        ("target", details.target.clone()),
+        (
+            "agent",
+            concat!("cargo-quickinstall/", env!("CARGO_PKG_VERSION")).to_string(),
+        ),
+        ("status", install_result_to_status_str(result).to_string()),
+    ]
+}
+
+/// Print what [`report_stats_in_background`] sends, or would send if `enabled`
+/// was true.
+pub fn print_stats(
+    details: &CrateDetails,
+    result: &Result<InstallSuccess, InstallError>,
+    enabled: bool,
+) {
+    let stats_url = get_stats_url("/record-install");
+    if enabled {
+        println!("Reporting this install with `POST {stats_url}`:");
+    } else {
+        println!(
+            "Telemetry is disabled, but this is what would be reported with `POST {stats_url}`:"
+        );
+    }
+    for (key, value) in stats_query_params(details, result) {
+        println!("    {key}={value}");
+    }
+}
+
 pub fn report_stats_in_background(
     details: &CrateDetails,
     result: &Result<InstallSuccess, InstallError>,
 ) {
-    let stats_url = format!(
-        "https://cargo-quickinstall-stats-server.fly.dev/record-install?crate={crate}&version={version}&target={target}&agent={agent}&status={status}",
-        crate = url_encode(&details.crate_name),
-        version = url_encode(&details.version),
-        target = url_encode(&details.target),
-        agent = url_encode(concat!("cargo-quickinstall/", env!("CARGO_PKG_VERSION"))),
-        status = install_result_to_status_str(result),
-    );
+    let query = stats_query_params(details, result)
+        .iter()
+        .map(|(key, value)| format!("{key}={}", url_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&");
+    let stats_url = format!("{}?{query}", get_stats_url("/record-install"));
 
     // Simply spawn the curl command to report stat.
     //
@@ -265,6 +488,33 @@ pub fn report_stats_in_background(
         .ok();
 }
 
+/// Ask our stats server to build a package for `details`.
+///
+/// Unlike [`report_stats_in_background`], this waits for the server to respond.
+pub fn request_build(details: &CrateDetails) -> Result<(), InstallError> {
+    let query = [
+        ("crate", details.crate_name.as_str()),
+        ("version", details.version.as_str()),
+        ("target", details.target.as_str()),
+        (
+            "agent",
+            concat!("cargo-quickinstall/", env!("CARGO_PKG_VERSION")),
+        ),
+    ]
+    .iter()
+    .map(|(key, value)| format!("{key}={}", url_encode(value)))
+    .collect::<Vec<_>>()
+    .join("&");
+    let url = format!("{}?{query}", get_stats_url("/request-build"));
+
+    let mut cmd = prepare_curl_post_cmd(&url);
+    add_curl_write_out(&mut cmd);
+    cmd.output_checked_status()
+        .map_err(|err| err.into_download_failed(&url))?;
+
+    Ok(())
+}
+
 fn url_encode(input: &str) -> String {
     let mut encoded = String::with_capacity(input.len());
 
@@ -290,50 +540,148 @@ pub fn do_dry_run_curl(
     crate_details: &CrateDetails,
     fallback: bool,
 ) -> Result<String, InstallError> {
-    let urls = get_quickinstall_download_urls(crate_details);
-
-    let (url, res) = match curl_head(&urls[0]) {
-        Err(err) if err.is_curl_404() => (&urls[1], curl_head(&urls[1])),
-        res => (&urls[0], res),
-    };
-
-    match res {
-        Ok(_) => {
+    match find_quickinstall_download_url(crate_details)? {
+        Some(url) => {
             let cargo_bin_dir = get_cargo_bin_dir()?;
 
-            Ok(format_curl_and_untar_cmd(url, &cargo_bin_dir))
+            Ok(format_curl_and_untar_cmd(&url, &cargo_bin_dir))
         }
-        Err(err) if err.is_curl_404() && fallback => {
+        None if fallback => {
             let cargo_install_cmd = prepare_cargo_install_cmd(crate_details);
             Ok(format!("{}", cargo_install_cmd.formattable()))
         }
+        None => Err(InstallError::NoFallback(crate_details.clone())),
+    }
+}
+
+/// Check both release url schemas with a HEAD request, returning the url of the
+/// pre-built package, or `None` if there isn't one.
+pub fn find_quickinstall_download_url(
+    crate_details: &CrateDetails,
+) -> Result<Option<String>, InstallError> {
+    head_quickinstall_package(crate_details).map(|res| res.map(|(url, _headers)| url))
+}
+
+/// Return (url, headers) of the pre-built package, trying both release url schemas.
+fn head_quickinstall_package(
+    crate_details: &CrateDetails,
+) -> Result<Option<(String, Vec<u8>)>, InstallError> {
+    let [url, old_url] = get_quickinstall_download_urls(crate_details);
+
+    match curl_head(&url) {
+        Ok(headers) => Ok(Some((url, headers))),
+        Err(err) if err.is_not_found() => match curl_head(&old_url) {
+            Ok(headers) => Ok(Some((old_url, headers))),
+            Err(err) if err.is_not_found() => Ok(None),
+            Err(err) => Err(err),
+        },
         Err(err) => Err(err),
     }
 }
 
-fn untar(mut curl: ChildWithCommand) -> Result<String, InstallError> {
-    let bin_dir = get_cargo_bin_dir()?;
+pub struct PackageInfo {
+    pub url: String,
+    /// Size of the tarball in bytes, if the server told us.
+    pub size: Option<u64>,
+    /// Whether there is a minisign signature next to the tarball.
+    pub signed: bool,
+}
 
-    let res = prepare_untar_cmd(&bin_dir)
-        .stdin(curl.stdout().take().unwrap())
-        .output_checked_status();
+/// Look up the pre-built package for `crate_details` without downloading it.
+pub fn get_quickinstall_package_info(
+    crate_details: &CrateDetails,
+) -> Result<Option<PackageInfo>, InstallError> {
+    let Some((url, headers)) = head_quickinstall_package(crate_details)? else {
+        return Ok(None);
+    };
 
-    // Propagate this error before Propagating error tar since
-    // if tar fails, it's likely due to curl failed.
-    //
-    // For example, this would enable the 404 error to be propagated
-    // correctly.
-    curl.wait_with_output_checked_status()?;
+    let signed = match curl_head(&format!("{url}.sig")) {
+        Ok(_) => true,
+        Err(err) if err.is_not_found() => false,
+        Err(err) => return Err(err),
+    };
 
-    let output = res?;
+    Ok(Some(PackageInfo {
+        size: parse_content_length(&utf8_to_string_lossy(headers)),
+        url,
+        signed,
+    }))
+}
 
+/// `curl --location` prints the headers of every response it gets, so we only
+/// look at the ones after the last status line, which belong to the final response.
+fn parse_content_length(headers: &str) -> Option<u64> {
+    headers
+        .lines()
+        .rev()
+        .take_while(|line| !line.starts_with("HTTP/"))
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                value.trim().parse().ok()
+            } else {
+                None
+            }
+        })
+}
+
+fn tar_output_to_string(output: process::Output) -> String {
     let stdout = utf8_to_string_lossy(output.stdout);
     let stderr = String::from_utf8_lossy(&output.stderr);
 
     let mut s = stdout;
     s += &stderr;
 
-    Ok(s)
+    s
+}
+
+/// Download the pre-built package for `details` and return the verbose `tar`
+/// listing of its contents, without extracting anything.
+pub fn list_quickinstall_package(details: &CrateDetails) -> Result<String, InstallError> {
+    let url = find_quickinstall_download_url(details)?
+        .ok_or_else(|| InstallError::NoFallback(details.clone()))?;
+
+    with_retries(&url, || {
+        let mut curl = curl(&url)?;
+
+        let res = prepare_list_tar_cmd(true)
+            .stdin(curl.stdout().take().unwrap())
+            .output_checked_status();
+
+        curl.wait_with_output_checked_status()
+            .map_err(|err| err.into_download_failed(&url))?;
+
+        Ok(utf8_to_string_lossy(
+            res.map_err(InstallError::into_extract_failed)?.stdout,
+        ))
+    })
+}
+
+fn untar_file(tarball: &Path) -> Result<String, InstallError> {
+    let bin_dir = get_cargo_bin_dir()?;
+
+    let output = prepare_untar_cmd(&bin_dir)
+        .stdin(File::open(tarball)?)
+        .output_checked_status()
+        .map_err(InstallError::into_extract_failed)?;
+
+    Ok(tar_output_to_string(output))
+}
+
+/// Returns the names of the files in `tarball`, without any leading directories.
+fn list_tar_file(tarball: &Path) -> Result<Vec<String>, InstallError> {
+    let output = prepare_list_tar_cmd(false)
+        .stdin(File::open(tarball)?)
+        .output_checked_status()
+        .map_err(InstallError::into_extract_failed)?;
+
+    Ok(utf8_to_string_lossy(output.stdout)
+        .lines()
+        .filter(|entry| !entry.ends_with('/'))
+        .filter_map(|entry| entry.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect())
 }
 
 fn prepare_curl_head_cmd(url: &str) -> std::process::Command {
@@ -343,39 +691,178 @@ fn prepare_curl_head_cmd(url: &str) -> std::process::Command {
 }
 
 fn prepare_curl_post_cmd(url: &str) -> std::process::Command {
+    verbose!("POST {url}");
     let mut cmd = prepare_curl_cmd();
     cmd.args(["-X", "POST"]).arg(url);
     cmd
 }
 
 fn curl_head(url: &str) -> Result<Vec<u8>, InstallError> {
-    prepare_curl_head_cmd(url)
-        .output_checked_status()
-        .map(|output| output.stdout)
+    with_retries(url, || {
+        verbose!("HEAD {url}");
+        let mut cmd = prepare_curl_head_cmd(url);
+        cmd.stdout(process::Stdio::piped());
+        spawn_curl(cmd, url)?
+            .wait_with_output_checked_status()
+            .map(|output| output.stdout)
+            .map_err(|err| err.into_download_failed(url))
+    })
 }
 
 fn curl(url: &str) -> Result<ChildWithCommand, InstallError> {
+    verbose!("GET {url}");
     let mut cmd = prepare_curl_bytes_cmd(url);
-    cmd.stdin(process::Stdio::null())
-        .stdout(process::Stdio::piped())
-        .stderr(process::Stdio::piped());
-    cmd.spawn_with_cmd()
+    cmd.stdout(process::Stdio::piped());
+    spawn_curl(cmd, url)
 }
 
-fn curl_file(url: &str, file: File) -> Result<(), InstallError> {
-    prepare_curl_bytes_cmd(url)
-        .stdin(process::Stdio::null())
-        .stdout(file)
-        .stderr(process::Stdio::piped())
-        .output_checked_status()?;
+/// Download `url` into `file`, showing our progress if stdout is a terminal.
+fn curl_file(url: &str, mut file: File) -> Result<(), InstallError> {
+    let label = url.rsplit('/').next().unwrap_or(url).to_string();
+
+    with_retries(url, || {
+        // Throw away whatever a previous attempt managed to download.
+        file.set_len(0)?;
+        file.rewind()?;
+
+        verbose!("GET {url}");
+        let mut cmd = prepare_curl_bytes_cmd(url);
+        let Some(progress) = Progress::new(label.clone()) else {
+            cmd.stdout(file.try_clone()?);
+            spawn_curl(cmd, url)?
+                .wait_with_output_checked_status()
+                .map_err(|err| err.into_download_failed(url))?;
+            return Ok(());
+        };
+
+        // Get the size from the headers of this same request, rather than with
+        // another HEAD.
+        let headers = NamedTempFile::new()?;
+        cmd.arg("--dump-header").arg(headers.path());
+        cmd.stdout(process::Stdio::piped());
+        let mut curl = spawn_curl(cmd, url)?;
+        let copied = progress.copy(curl.stdout().take().unwrap(), &file, || {
+            let headers = fs::read(headers.path()).ok()?;
+            parse_content_length(&utf8_to_string_lossy(headers))
+        });
+        // If curl failed, that's more interesting than us failing to copy what it
+        // managed to download.
+        curl.wait_with_output_checked_status()
+            .map_err(|err| err.into_download_failed(url))?;
+        copied?;
 
-    Ok(())
+        Ok(())
+    })
 }
 
 fn curl_bytes(url: &str) -> Result<Vec<u8>, InstallError> {
-    prepare_curl_bytes_cmd(url)
+    with_retries(url, || {
+        verbose!("GET {url}");
+        let mut cmd = prepare_curl_bytes_cmd(url);
+        cmd.stdout(process::Stdio::piped());
+        spawn_curl(cmd, url)?
+            .wait_with_output_checked_status()
+            .map(|output| output.stdout)
+            .map_err(|err| err.into_download_failed(url))
+    })
+}
+
+/// Start a curl `cmd` for `url`, with stderr piped so that
+/// [`InstallError::into_download_failed`] can find the HTTP status in it.
+///
+/// If `url` is on GitHub and we have a token, we pass it to curl on stdin, so
+/// that it doesn't show up in `ps`, or in the command in our error messages.
+fn spawn_curl(mut cmd: process::Command, url: &str) -> Result<ChildWithCommand, InstallError> {
+    let auth_config = settings()
+        .github_token
+        .as_deref()
+        .filter(|_| is_github_url(url))
+        .map(curl_auth_config);
+
+    add_curl_write_out(&mut cmd);
+    cmd.stderr(process::Stdio::piped());
+    if auth_config.is_some() {
+        cmd.args(["--config", "-"]).stdin(process::Stdio::piped());
+    } else {
+        cmd.stdin(process::Stdio::null());
+    }
+
+    let mut curl = cmd.spawn_with_cmd()?;
+    if let Some(auth_config) = auth_config {
+        // This is much smaller than a pipe buffer, so it can't block.
+        curl.stdin()
+            .take()
+            .unwrap()
+            .write_all(auth_config.as_bytes())?;
+    }
+
+    Ok(curl)
+}
+
+/// Have curl print the HTTP status to stderr for
+/// [`InstallError::into_download_failed`], if it is new enough to do that.
+fn add_curl_write_out(cmd: &mut process::Command) {
+    static CURL_VERSION: OnceLock<Option<(u32, u32)>> = OnceLock::new();
+
+    let write_out = CURL_VERSION
+        .get_or_init(get_curl_version)
+        .and_then(curl_write_out_for_version);
+    if let Some(write_out) = write_out {
+        cmd.arg("--write-out").arg(write_out);
+    }
+}
+
+/// Before 7.63, `--write-out` could only write to stdout, where it would end up
+/// in whatever we are downloading.
+fn curl_write_out_for_version(version: (u32, u32)) -> Option<&'static str> {
+    if version >= (7, 84) {
+        Some(CURL_WRITE_OUT_HTTP_STATUS)
+    } else if version >= (7, 63) {
+        Some(CURL_WRITE_OUT_HTTP_STATUS_ONLY)
+    } else {
+        None
+    }
+}
+
+fn get_curl_version() -> Option<(u32, u32)> {
+    let output = process::Command::new("curl")
+        .arg("--version")
         .output_checked_status()
-        .map(|output| output.stdout)
+        .ok()?;
+    parse_curl_version(&utf8_to_string_lossy(output.stdout))
+}
+
+/// Returns the major and minor version from `curl --version`, which starts with
+/// something like `curl 7.58.0 (x86_64-pc-linux-gnu) libcurl/7.58.0 ...`.
+fn parse_curl_version(output: &str) -> Option<(u32, u32)> {
+    let version = output.strip_prefix("curl ")?.split_whitespace().next()?;
+    let mut parts = version.split('.');
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
+/// Whether `url` is on github.com over https, which is the only place we send
+/// our GitHub token. curl drops the header if it gets redirected to another host.
+fn is_github_url(url: &str) -> bool {
+    let Some(rest) = url.strip_prefix("https://") else {
+        return false;
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    if authority.contains('@') {
+        return false;
+    }
+    let host = authority
+        .split(':')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    host == "github.com" || host.ends_with(".github.com")
+}
+
+/// A curl config file that sends `token` to GitHub.
+fn curl_auth_config(token: &str) -> String {
+    let token = token.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("header = \"Authorization: Bearer {token}\"\n")
 }
 
 fn curl_string(url: &str) -> Result<String, InstallError> {
@@ -391,6 +878,9 @@ pub fn curl_json(url: &str) -> Result<JsonValue, InstallError> {
         })
 }
 
+const QUICKINSTALL_RELEASES_URL: &str =
+    "https://github.com/cargo-bins/cargo-quickinstall/releases/download";
+
 fn get_quickinstall_download_urls(
     CrateDetails {
         crate_name,
@@ -398,15 +888,25 @@ fn get_quickinstall_download_urls(
         target,
     }: &CrateDetails,
 ) -> [String; 2] {
-    [format!(
-        "https://github.com/cargo-bins/cargo-quickinstall/releases/download/{crate_name}-{version}/{crate_name}-{version}-{target}.tar.gz",
-    ),
-    format!(
-        "https://github.com/cargo-bins/cargo-quickinstall/releases/download/{crate_name}-{version}-{target}/{crate_name}-{version}-{target}.tar.gz",
-    )]
+    let base_url = settings()
+        .mirror
+        .as_deref()
+        .unwrap_or(QUICKINSTALL_RELEASES_URL)
+        .trim_end_matches('/');
+
+    [
+        format!("{base_url}/{crate_name}-{version}/{crate_name}-{version}-{target}.tar.gz",),
+        format!(
+            "{base_url}/{crate_name}-{version}-{target}/{crate_name}-{version}-{target}.tar.gz",
+        ),
+    ]
 }
 
 fn prepare_curl_cmd() -> std::process::Command {
+    prepare_curl_cmd_with(settings(), ca_bundle())
+}
+
+fn prepare_curl_cmd_with(settings: &Settings, ca_bundle: Option<&Path>) -> std::process::Command {
     let mut cmd = std::process::Command::new("curl");
     cmd.args([
         "--user-agent",
@@ -420,9 +920,73 @@ fn prepare_curl_cmd() -> std::process::Command {
         "--show-error",
         "--fail",
     ]);
+
+    if let Some(proxy) = &settings.proxy {
+        cmd.arg("--proxy").arg(proxy);
+    }
+    if let Some(no_proxy) = &settings.no_proxy {
+        cmd.arg("--noproxy").arg(no_proxy);
+    }
+    if let Some(ca_bundle) = ca_bundle {
+        cmd.arg("--cacert").arg(ca_bundle);
+    }
+    if let Some(connect_timeout) = settings.connect_timeout {
+        cmd.arg("--connect-timeout")
+            .arg(connect_timeout.to_string());
+    }
+    if let Some(timeout) = settings.timeout {
+        cmd.arg("--max-time").arg(timeout.to_string());
+    }
+    if let Some(max_download_size) = settings.max_download_size {
+        cmd.arg("--max-filesize").arg(max_download_size.to_string());
+    }
+
     cmd
 }
 
+/// Pass our proxy and CA settings on to `cargo binstall`, which makes its own
+/// requests without curl.
+pub fn set_binstall_network_env(cmd: &mut process::Command) {
+    set_binstall_network_env_with(cmd, settings(), ca_bundle());
+}
+
+fn set_binstall_network_env_with(
+    cmd: &mut process::Command,
+    settings: &Settings,
+    ca_bundle: Option<&Path>,
+) {
+    if let Some(proxy) = &settings.proxy {
+        cmd.env("HTTPS_PROXY", proxy).env("HTTP_PROXY", proxy);
+    }
+    if let Some(no_proxy) = &settings.no_proxy {
+        cmd.env("NO_PROXY", no_proxy);
+    }
+    if let Some(ca_bundle) = ca_bundle {
+        cmd.env("SSL_CERT_FILE", ca_bundle);
+    }
+}
+
+/// Pass our proxy, CA and timeout settings on to `cargo install`.
+fn set_cargo_network_env_with(
+    cmd: &mut process::Command,
+    settings: &Settings,
+    ca_bundle: Option<&Path>,
+) {
+    if let Some(proxy) = &settings.proxy {
+        cmd.env("CARGO_HTTP_PROXY", proxy);
+    }
+    // cargo doesn't have its own setting for this, but libcurl reads it.
+    if let Some(no_proxy) = &settings.no_proxy {
+        cmd.env("NO_PROXY", no_proxy);
+    }
+    if let Some(ca_bundle) = ca_bundle {
+        cmd.env("CARGO_HTTP_CAINFO", ca_bundle);
+    }
+    if let Some(timeout) = settings.timeout {
+        cmd.env("CARGO_HTTP_TIMEOUT", timeout.to_string());
+    }
+}
+
 fn prepare_curl_bytes_cmd(url: &str) -> std::process::Command {
     let mut cmd = prepare_curl_cmd();
     cmd.arg(url);
@@ -435,11 +999,193 @@ fn prepare_untar_cmd(cargo_bin_dir: &Path) -> std::process::Command {
     cmd
 }
 
+fn prepare_list_tar_cmd(verbose: bool) -> std::process::Command {
+    let mut cmd = std::process::Command::new("tar");
+    cmd.arg(if verbose { "-tzvf" } else { "-tzf" }).arg("-");
+    cmd
+}
+
 fn prepare_cargo_install_cmd(details: &CrateDetails) -> std::process::Command {
     let mut cmd = std::process::Command::new("cargo");
     cmd.arg("install")
         .arg(&details.crate_name)
         .arg("--version")
         .arg(&details.version);
+    match verbosity() {
+        Verbosity::Quiet => {
+            cmd.arg("--quiet");
+        }
+        Verbosity::VeryVerbose => {
+            cmd.arg("--verbose");
+        }
+        Verbosity::Normal | Verbosity::Verbose => {}
+    }
+    set_cargo_network_env_with(&mut cmd, settings(), ca_bundle());
     cmd
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supported_targets_in_sync() {
+        let supported_targets =
+            std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/../supported-targets"))
+                .unwrap();
+
+        assert_eq!(
+            supported_targets.split_whitespace().collect::<Vec<_>>(),
+            SUPPORTED_TARGETS
+        );
+    }
+
+    #[test]
+    fn test_stats_query_params() {
+        let details = CrateDetails {
+            crate_name: "ripgrep".to_string(),
+            version: "14.1.1".to_string(),
+            target: "x86_64-unknown-linux-gnu".to_string(),
+        };
+
+        let params = stats_query_params(&details, &Ok(InstallSuccess::InstalledFromTarball));
+
+        assert_eq!(
+            params.each_ref().map(|(key, _)| *key),
+            ["crate", "version", "target", "agent", "status"]
+        );
+        assert_eq!(params[4].1, "installed-from-tarball");
+    }
+
+    #[test]
+    fn test_prepare_curl_cmd() {
+        let settings = Settings {
+            proxy: Some("http://proxy.example.com:3128".to_string()),
+            ca_cert: Some("/etc/ssl/certs/corp.pem".into()),
+            timeout: Some(60),
+            max_download_size: Some(1024),
+            ..Settings::default()
+        };
+
+        let ca_bundle = Path::new("/etc/ssl/certs/corp.pem");
+
+        let cmd = prepare_curl_cmd_with(&settings, Some(ca_bundle))
+            .formattable()
+            .to_string();
+        assert!(
+            cmd.ends_with(
+                " --fail --proxy \"http://proxy.example.com:3128\" --cacert /etc/ssl/certs/corp.pem \
+                 --max-time 60 --max-filesize 1024"
+            ),
+            "{}",
+            cmd
+        );
+    }
+
+    #[test]
+    fn test_set_network_env() {
+        let settings = Settings {
+            proxy: Some("http://proxy.example.com:3128".to_string()),
+            no_proxy: Some("localhost".to_string()),
+            timeout: Some(60),
+            ..Settings::default()
+        };
+        let ca_bundle = Path::new("/root/.cargo/.quickinstall-ca-bundle.pem");
+        let env = |cmd: &process::Command, key: &str| {
+            cmd.get_envs()
+                .find(|(k, _)| *k == key)
+                .and_then(|(_, value)| value?.to_str())
+                .map(str::to_string)
+        };
+
+        let mut cmd = process::Command::new("cargo");
+        set_binstall_network_env_with(&mut cmd, &settings, Some(ca_bundle));
+        assert_eq!(
+            env(&cmd, "HTTPS_PROXY").as_deref(),
+            Some("http://proxy.example.com:3128")
+        );
+        assert_eq!(env(&cmd, "NO_PROXY").as_deref(), Some("localhost"));
+        assert_eq!(env(&cmd, "SSL_CERT_FILE").as_deref(), ca_bundle.to_str());
+
+        let mut cmd = process::Command::new("cargo");
+        set_cargo_network_env_with(&mut cmd, &settings, Some(ca_bundle));
+        assert_eq!(
+            env(&cmd, "CARGO_HTTP_PROXY").as_deref(),
+            Some("http://proxy.example.com:3128")
+        );
+        assert_eq!(
+            env(&cmd, "CARGO_HTTP_CAINFO").as_deref(),
+            ca_bundle.to_str()
+        );
+        assert_eq!(env(&cmd, "CARGO_HTTP_TIMEOUT").as_deref(), Some("60"));
+    }
+
+    #[test]
+    fn test_is_github_url() {
+        assert!(is_github_url(
+            "https://github.com/cargo-bins/cargo-quickinstall/releases/download/a/b.tar.gz"
+        ));
+        assert!(is_github_url("https://api.github.com/repos"));
+        assert!(is_github_url("https://GitHub.com:443"));
+
+        assert!(!is_github_url("http://github.com/cargo-bins"));
+        assert!(!is_github_url("https://crates.io/api/v1/crates/ripgrep"));
+        assert!(!is_github_url("https://github.com.example.com/"));
+        assert!(!is_github_url("https://github.com@example.com/"));
+        assert!(!is_github_url("https://example.com/?github.com"));
+        assert!(!is_github_url("https://notgithub.com/"));
+    }
+
+    #[test]
+    fn test_curl_auth_config() {
+        assert_eq!(
+            curl_auth_config("ghp_abc123"),
+            "header = \"Authorization: Bearer ghp_abc123\"\n"
+        );
+        assert_eq!(
+            curl_auth_config(r#"a"b\c"#),
+            "header = \"Authorization: Bearer a\\\"b\\\\c\"\n"
+        );
+    }
+
+    #[test]
+    fn test_curl_write_out_for_version() {
+        let version = parse_curl_version(
+            "curl 7.58.0 (x86_64-pc-linux-gnu) libcurl/7.58.0 OpenSSL/1.1.1\nRelease-Date: 2018-01-24\n",
+        );
+        assert_eq!(version, Some((7, 58)));
+        assert_eq!(curl_write_out_for_version((7, 58)), None);
+        assert_eq!(
+            curl_write_out_for_version((7, 68)),
+            Some(CURL_WRITE_OUT_HTTP_STATUS_ONLY)
+        );
+        assert_eq!(
+            curl_write_out_for_version((8, 5)),
+            Some(CURL_WRITE_OUT_HTTP_STATUS)
+        );
+        assert_eq!(parse_curl_version("not curl"), None);
+    }
+
+    #[test]
+    fn test_help_mentions_flag() {
+        let help =
+            "Options:\n      --disable-telemetry  Disable statistics collection\n  -q, --quiet";
+
+        assert!(help_mentions_flag(help, "--disable-telemetry"));
+        assert!(help_mentions_flag(help, "--quiet"));
+        assert!(!help_mentions_flag(help, "--disable"));
+        assert!(!help_mentions_flag(help, "--verbose"));
+    }
+
+    #[test]
+    fn test_parse_content_length() {
+        let headers = "HTTP/2 302 \r\nlocation: https://example.com\r\ncontent-length: 0\r\n\r\n\
+            HTTP/2 200 \r\nContent-Length: 1234\r\n\r\n";
+
+        assert_eq!(parse_content_length(headers), Some(1234));
+        assert_eq!(parse_content_length("HTTP/2 200 \r\n\r\n"), None);
+        // The redirect's length isn't the length of the download.
+        let headers = "HTTP/2 302 \r\ncontent-length: 0\r\n\r\nHTTP/2 200 \r\n\r\n";
+        assert_eq!(parse_content_length(headers), None);
+    }
+}
@@ -11,7 +11,12 @@ use cargo_quickinstall::install_error::*;
 use cargo_quickinstall::*;
 
 mod args;
-use args::Crate;
+use args::{Crate, Subcommand};
+
+mod config;
+
+/// How many versions `cargo quickinstall info` checks when none is specified.
+const INFO_VERSIONS: usize = 5;
 
 fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
     let options = args::options_from_cli_args(pico_args::Arguments::from_env())?;
@@ -29,6 +34,27 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
         return Ok(());
     }
 
+    if let Some(Subcommand::ConfigShow) = options.subcommand {
+        print_config(&options);
+        return Ok(());
+    }
+
+    let custom_stats_server = options.stats_server.is_some();
+
+    init_settings(Settings {
+        mirror: options.mirror,
+        stats_server: options.stats_server,
+        retries: options.retries,
+        github_token: options.github_token,
+        proxy: options.proxy,
+        no_proxy: options.no_proxy,
+        ca_cert: options.ca_cert,
+        connect_timeout: options.connect_timeout,
+        timeout: options.timeout,
+        max_download_size: options.max_download_size,
+        verbosity: options.verbosity,
+    });
+
     let crate_names = options.crate_names;
 
     if crate_names.is_empty() {
@@ -37,13 +63,59 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
 
     let target = options.target;
 
+    if let Some(Subcommand::Info) = options.subcommand {
+        let targets = match target.as_deref() {
+            Some(target) => {
+                args::multiple_targets(target).unwrap_or_else(|| vec![target.to_string()])
+            }
+            None => args::multiple_targets("all").unwrap(),
+        };
+
+        return do_info(crate_names, targets, options.jobs);
+    }
+
+    if let Some(Subcommand::Request) = options.subcommand {
+        let targets = match target.as_deref() {
+            Some(target) => {
+                args::multiple_targets(target).unwrap_or_else(|| vec![target.to_string()])
+            }
+            None => vec![get_target_triple()?],
+        };
+
+        return do_request(crate_names, targets);
+    }
+
+    if options.list_files {
+        if target.as_deref().and_then(args::multiple_targets).is_some() {
+            Err("`--target all` and lists of targets are not supported with `--list-files`")?
+        }
+
+        return do_list_files(crate_names, target);
+    }
+
     let args = Args {
         dry_run: options.dry_run,
         try_upstream: options.try_upstream,
         fallback: options.fallback,
         force: options.force,
+        telemetry: options.telemetry,
+        print_telemetry: options.print_telemetry,
+        custom_stats_server,
+        jobs: options.jobs,
     };
 
+    if args.print_telemetry && !options.no_binstall {
+        Err("`--print-telemetry` requires `--no-binstall`, since `cargo-binstall` sends its own reports")?
+    }
+
+    if let Some(targets) = target.as_deref().and_then(args::multiple_targets) {
+        if !args.dry_run {
+            Err("`--target all` and lists of targets are only supported with `--dry-run`")?
+        }
+
+        return do_dry_run_matrix(crate_names, targets, args);
+    }
+
     let f = if options.no_binstall {
         do_main_curl
     } else {
@@ -59,6 +131,93 @@ struct Args {
     try_upstream: bool,
     fallback: bool,
     force: bool,
+    telemetry: bool,
+    print_telemetry: bool,
+    /// Set with `--stats-server`, which cargo-binstall doesn't know about.
+    custom_stats_server: bool,
+    jobs: usize,
+}
+
+fn print_config(options: &args::CliOptions) {
+    match &options.config_path {
+        Some(path) => println!("# Config file: {}", path.display()),
+        None => println!("# No config file"),
+    }
+
+    let print = |key: &str, value: Option<String>| {
+        let source = options.sources[key];
+        match value {
+            Some(value) => println!("{key} = {value} # {source}"),
+            None => println!("# {key} is not set"),
+        }
+    };
+
+    print("target", options.target.as_ref().map(|t| format!("{t:?}")));
+    print("fallback", Some(options.fallback.to_string()));
+    print("binstall", Some((!options.no_binstall).to_string()));
+    print("mirror", options.mirror.as_ref().map(|m| format!("{m:?}")));
+    print("telemetry", Some(options.telemetry.to_string()));
+    print(
+        "stats_server",
+        options.stats_server.as_ref().map(|s| format!("{s:?}")),
+    );
+    print("jobs", Some(options.jobs.to_string()));
+    print("retries", Some(options.retries.to_string()));
+    // Don't print the token itself, in case this ends up in a CI log.
+    print(
+        "github_token",
+        options
+            .github_token
+            .as_ref()
+            .map(|_| "\"<redacted>\"".to_string()),
+    );
+    print("proxy", options.proxy.as_ref().map(|p| format!("{p:?}")));
+    print(
+        "no_proxy",
+        options.no_proxy.as_ref().map(|n| format!("{n:?}")),
+    );
+    print(
+        "ca_cert",
+        options
+            .ca_cert
+            .as_ref()
+            .map(|c| format!("{:?}", c.display())),
+    );
+    print(
+        "connect_timeout",
+        options.connect_timeout.map(|t| t.to_string()),
+    );
+    print("timeout", options.timeout.map(|t| t.to_string()));
+    print(
+        "max_download_size",
+        options.max_download_size.map(|s| s.to_string()),
+    );
+    print(
+        "quiet",
+        Some((options.verbosity == Verbosity::Quiet).to_string()),
+    );
+}
+
+/// Run `f` on each of `items`, at most `jobs` at a time, returning the results in order.
+fn parallel_map<T: Sync, R: Send>(items: &[T], jobs: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let f = &f;
+
+    std::thread::scope(|scope| {
+        items
+            .chunks(jobs)
+            .flat_map(|chunk| {
+                let handles = chunk
+                    .iter()
+                    .map(|item| scope.spawn(move || f(item)))
+                    .collect::<Vec<_>>();
+
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    })
 }
 
 fn do_main_curl(
@@ -91,8 +250,19 @@ fn do_main_curl(
             let shell_cmd = do_dry_run_curl(&crate_details, args.fallback)?;
             println!("{shell_cmd}");
         } else {
-            let result = install_crate_curl(&crate_details, args.fallback);
-            report_stats_in_background(&crate_details, &result);
+            let result = install_crate_curl(&crate_details, args.fallback, args.force);
+            if args.print_telemetry {
+                print_stats(&crate_details, &result, args.telemetry);
+            }
+            if args.telemetry {
+                report_stats_in_background(&crate_details, &result);
+            }
+            if matches!(
+                result,
+                Ok(InstallSuccess::BuiltFromSource) | Err(InstallError::CargoInstallFailed)
+            ) {
+                suggest_requesting_build(&crate_details, args.telemetry);
+            }
             result?;
         }
     }
@@ -100,6 +270,187 @@ fn do_main_curl(
     Ok(())
 }
 
+/// After falling back to `cargo install`, tell the user how the missing package
+/// gets built, depending on whether we just reported the install.
+fn suggest_requesting_build(details: &CrateDetails, reported: bool) {
+    if reported {
+        status!("We have reported your installation request, so it should be built soon.");
+        status!(
+            "You can also ask for it explicitly with `cargo quickinstall request {}@{} --target {}`.",
+            details.crate_name, details.version, details.target
+        );
+    } else {
+        status!(
+            "Telemetry is disabled, so we haven't reported that {}@{} is missing on {}.",
+            details.crate_name,
+            details.version,
+            details.target
+        );
+        status!(
+            "You can ask for it to be built with `cargo quickinstall request {}@{} --target {}`.",
+            details.crate_name,
+            details.version,
+            details.target
+        );
+    }
+}
+
+/// Print which of `targets` have a pre-built package for each crate, and what
+/// would happen for the ones that don't.
+fn do_dry_run_matrix(
+    crates: Vec<Crate>,
+    targets: Vec<String>,
+    args: Args,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let width = targets.iter().map(String::len).max().unwrap_or_default();
+
+    for Crate {
+        name: crate_name,
+        version,
+    } in crates
+    {
+        let version = match version {
+            Some(version) => version,
+            None => get_latest_version(&crate_name)?,
+        };
+
+        println!("{crate_name}@{version}:");
+
+        let results = parallel_map(&targets, args.jobs, |target| {
+            find_quickinstall_download_url(&CrateDetails {
+                crate_name: crate_name.clone(),
+                version: version.clone(),
+                target: target.clone(),
+            })
+        });
+
+        for (target, result) in targets.iter().zip(results) {
+            match result {
+                Ok(Some(url)) => println!("    {target:width$}  prebuilt  {url}"),
+                Ok(None) if args.fallback => {
+                    println!("    {target:width$}  fallback  cargo install")
+                }
+                Ok(None) => println!("    {target:width$}  missing"),
+                Err(err) => println!("    {target:width$}  error     {err}"),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn do_info(
+    crates: Vec<Crate>,
+    targets: Vec<String>,
+    jobs: usize,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let width = targets.iter().map(String::len).max().unwrap_or_default();
+
+    for Crate {
+        name: crate_name,
+        version,
+    } in crates
+    {
+        let versions = match version {
+            Some(version) => vec![version],
+            None => get_recent_versions(&crate_name, INFO_VERSIONS)?,
+        };
+
+        for version in versions {
+            println!("{crate_name}@{version}:");
+
+            // Each target needs a few HEAD requests, so do them in parallel.
+            let results = parallel_map(&targets, jobs, |target| {
+                get_quickinstall_package_info(&CrateDetails {
+                    crate_name: crate_name.clone(),
+                    version: version.clone(),
+                    target: target.clone(),
+                })
+            });
+
+            for (target, result) in targets.iter().zip(results) {
+                match result {
+                    Ok(Some(PackageInfo { url, size, signed })) => println!(
+                        "    {target:width$}  prebuilt  {size:>10}  {signature:8}  {url}",
+                        size = size.map(format_size).unwrap_or_else(|| "?".to_string()),
+                        signature = if signed { "signed" } else { "unsigned" },
+                    ),
+                    Ok(None) => println!("    {target:width$}  missing"),
+                    Err(err) => println!("    {target:width$}  error     {err}"),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn do_request(
+    crates: Vec<Crate>,
+    targets: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    for Crate {
+        name: crate_name,
+        version,
+    } in crates
+    {
+        let version = match version {
+            Some(version) => version,
+            None => get_latest_version(&crate_name)?,
+        };
+
+        for target in &targets {
+            request_build(&CrateDetails {
+                crate_name: crate_name.clone(),
+                version: version.clone(),
+                target: target.clone(),
+            })?;
+
+            status!("Requested a build of {crate_name}@{version} for {target}");
+        }
+    }
+
+    Ok(())
+}
+
+fn do_list_files(
+    crates: Vec<Crate>,
+    target: Option<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let target = match target {
+        Some(target) => target,
+        None => get_target_triple()?,
+    };
+
+    for Crate {
+        name: crate_name,
+        version,
+    } in crates
+    {
+        let version = match version {
+            Some(version) => version,
+            None => get_latest_version(&crate_name)?,
+        };
+
+        let crate_details = CrateDetails {
+            crate_name,
+            version,
+            target: target.clone(),
+        };
+
+        // tar output contains its own newline.
+        print!(
+            "Files in {crate_name}@{version} for {target}:\n{listing}",
+            crate_name = crate_details.crate_name,
+            version = crate_details.version,
+            target = crate_details.target,
+            listing = list_quickinstall_package(&crate_details)?,
+        );
+    }
+
+    Ok(())
+}
+
 fn do_main_binstall(
     mut crates: Vec<Crate>,
     target: Option<String>,
@@ -130,9 +481,13 @@ fn do_main_binstall(
             try_upstream: true,
             fallback: false,
             force: true,
+            telemetry: args.telemetry,
+            print_telemetry: args.print_telemetry,
+            custom_stats_server: args.custom_stats_server,
+            jobs: args.jobs,
         };
 
-        download_and_install_binstall(args.dry_run)?;
+        download_and_install_binstall(&args)?;
 
         if args.dry_run {
             // cargo-binstall is not installed, so we print out the cargo-binstall
@@ -140,7 +495,7 @@ fn do_main_binstall(
             println!("cargo binstall --no-confirm --force cargo-binstall");
             return do_install_binstall(crates, target, BinstallMode::PrintCmd, args);
         } else {
-            println!(
+            status!(
                 "Bootstrapping cargo-binstall with itself to make `cargo uninstall cargo-binstall` work properly"
             );
             do_install_binstall(
@@ -156,7 +511,7 @@ fn do_main_binstall(
     }
 
     if crates.is_empty() {
-        println!("No crate to install");
+        status!("No crate to install");
 
         Ok(())
     } else {
@@ -165,17 +520,17 @@ fn do_main_binstall(
 }
 
 fn download_and_install_binstall(
-    dry_run: bool,
+    args: &Args,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
     let target = get_target_triple()?;
 
-    if dry_run {
+    if args.dry_run {
         return match do_dry_run_download_and_install_binstall_from_upstream(&target) {
             Ok(shell_cmd) => {
                 println!("{shell_cmd}");
                 Ok(())
             }
-            Err(err) if err.is_curl_404() => do_main_curl(
+            Err(err) if err.is_not_found() => do_main_curl(
                 vec![Crate {
                     name: "cargo-binstall".to_string(),
                     version: None,
@@ -191,8 +546,8 @@ fn download_and_install_binstall(
     }
 
     match download_and_install_binstall_from_upstream(&target) {
-        Err(err) if err.is_curl_404() => {
-            println!(
+        Err(err) if err.is_not_found() => {
+            status!(
                 "Failed to install cargo-binstall from upstream, fallback to quickinstall: {err}"
             );
 
@@ -202,7 +557,11 @@ fn download_and_install_binstall(
                     version: None,
                 }],
                 Some(target),
-                Args::default(),
+                Args {
+                    telemetry: args.telemetry,
+                    print_telemetry: args.print_telemetry,
+                    ..Default::default()
+                },
             )
         }
         res => res.map_err(From::from),
@@ -225,6 +584,8 @@ fn do_install_binstall(
 
     cmd.arg("binstall").arg("--no-confirm").arg("--no-symlinks");
 
+    set_binstall_network_env(&mut cmd);
+
     if let Some(target) = target {
         cmd.arg("--targets").arg(target);
     }
@@ -245,6 +606,27 @@ fn do_install_binstall(
         cmd.args(["--disable-strategies", "compile"]);
     }
 
+    // When printing the command, cargo-binstall isn't installed yet, so the command
+    // is for the latest version, which has every flag we use.
+    let supports = |flag| matches!(mode, BinstallMode::PrintCmd) || cargo_binstall_supports(flag);
+
+    // Versions of cargo-binstall from before `--disable-telemetry` don't have any
+    // telemetry to disable. cargo-binstall can only report to our stats server, so
+    // with `--stats-server` it mustn't report at all.
+    if (!args.telemetry || args.custom_stats_server) && supports("--disable-telemetry") {
+        cmd.arg("--disable-telemetry");
+    }
+
+    match verbosity() {
+        Verbosity::Quiet if supports("--quiet") => {
+            cmd.arg("--quiet");
+        }
+        Verbosity::VeryVerbose if supports("--verbose") => {
+            cmd.arg("--verbose");
+        }
+        _ => {}
+    }
+
     cmd.args(crates.into_iter().map(Crate::into_arg));
 
     if matches!(mode, BinstallMode::PrintCmd) {
@@ -252,14 +634,15 @@ fn do_install_binstall(
         return Ok(());
     }
 
-    println!("Calling `cargo-binstall` to do the install");
+    status!("Calling `cargo-binstall` to do the install");
+    very_verbose!("Running {}", cmd.formattable());
 
     #[cfg(unix)]
     if !matches!(mode, BinstallMode::Bootstrapping) {
         return Err(std::os::unix::process::CommandExt::exec(&mut cmd).into());
     }
 
-    let status = cmd.status()?;
+    let status = timed("cargo binstall", || cmd.status())?;
 
     if !status.success() {
         Err(format!("`{}` failed with {status}", cmd.formattable()).into())
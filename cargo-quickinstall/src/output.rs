@@ -0,0 +1,63 @@
+//! How much we print, set with `-q` and `-v`.
+//!
+//! Anything the user explicitly asked for, like the output of `--dry-run` or `info`,
+//! is printed regardless. Everything else goes through these macros. Verbose output
+//! goes to stderr, so that it can't end up in a script that reads our stdout.
+
+use std::time::Instant;
+
+use crate::settings::settings;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// `-q`: only print errors.
+    Quiet,
+    #[default]
+    Normal,
+    /// `-v`: also print every url we request, and how long each phase took.
+    Verbose,
+    /// `-vv`: also print every command we run.
+    VeryVerbose,
+}
+
+pub fn verbosity() -> Verbosity {
+    settings().verbosity
+}
+
+/// `println!`, unless we are in quiet mode.
+#[macro_export]
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::verbosity() >= $crate::Verbosity::Normal {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// `eprintln!`, with `-v`.
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::verbosity() >= $crate::Verbosity::Verbose {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// `eprintln!`, with `-vv`.
+#[macro_export]
+macro_rules! very_verbose {
+    ($($arg:tt)*) => {
+        if $crate::verbosity() >= $crate::Verbosity::VeryVerbose {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Run `f`, and print how long it took with `-v`.
+pub fn timed<T>(phase: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let res = f();
+    verbose!("{phase} took {:.2?}", start.elapsed());
+    res
+}
@@ -0,0 +1,147 @@
+//! A one-line progress indicator for downloads, so that big packages on slow links
+//! don't look like we've hung.
+
+use std::{
+    io::{self, IsTerminal, Read, Write},
+    time::{Duration, Instant},
+};
+
+use crate::{format_size, verbosity, Verbosity};
+
+/// How often we redraw the progress line.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+pub(crate) struct Progress {
+    label: String,
+    total: Option<u64>,
+    done: u64,
+    start: Instant,
+    last_draw: Option<Instant>,
+    /// The length of the last line we drew, so that we can clear it.
+    drawn_len: usize,
+}
+
+impl Progress {
+    /// Whether we show progress at all: not with `-q`, and stdout has to be a
+    /// terminal, so that we don't fill logs with it.
+    pub(crate) fn enabled() -> bool {
+        verbosity() > Verbosity::Quiet && io::stdout().is_terminal()
+    }
+
+    /// Returns `None` unless [`Progress::enabled`].
+    pub(crate) fn new(label: String) -> Option<Self> {
+        if !Self::enabled() {
+            return None;
+        }
+
+        Some(Self {
+            label,
+            total: None,
+            done: 0,
+            start: Instant::now(),
+            last_draw: None,
+            drawn_len: 0,
+        })
+    }
+
+    /// Copy everything from `reader` to `writer`, updating the progress line as we go,
+    /// and clear it once we're done.
+    ///
+    /// `total` is called once the first bytes have arrived, since that's when curl
+    /// is sure to have written out the response headers with the size.
+    pub(crate) fn copy(
+        mut self,
+        mut reader: impl Read,
+        mut writer: impl Write,
+        total: impl FnOnce() -> Option<u64>,
+    ) -> io::Result<u64> {
+        let mut total = Some(total);
+        let mut buf = [0; 64 * 1024];
+        let res = loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => break writer.flush().map(|()| self.done),
+                Ok(n) => n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => break Err(err),
+            };
+            if let Err(err) = writer.write_all(&buf[..n]) {
+                break Err(err);
+            }
+            if let Some(total) = total.take() {
+                self.total = total();
+            }
+            self.done += n as u64;
+            self.draw();
+        };
+
+        self.clear();
+        res
+    }
+
+    fn draw(&mut self) {
+        let now = Instant::now();
+        if self
+            .last_draw
+            .is_some_and(|last_draw| now - last_draw < REDRAW_INTERVAL)
+        {
+            return;
+        }
+        self.last_draw = Some(now);
+
+        let line = format!(
+            "{}: {}",
+            self.label,
+            format_progress(self.done, self.total, now - self.start)
+        );
+        let padding = self.drawn_len.saturating_sub(line.len());
+        self.drawn_len = line.len();
+
+        let mut stdout = io::stdout().lock();
+        write!(stdout, "\r{line}{:padding$}", "").ok();
+        stdout.flush().ok();
+    }
+
+    fn clear(&self) {
+        if self.drawn_len > 0 {
+            let mut stdout = io::stdout().lock();
+            write!(stdout, "\r{:width$}\r", "", width = self.drawn_len).ok();
+            stdout.flush().ok();
+        }
+    }
+}
+
+/// e.g. `1.5 MiB / 4.0 MiB, 512.0 KiB/s, ETA 5s`.
+fn format_progress(done: u64, total: Option<u64>, elapsed: Duration) -> String {
+    let rate = done as f64 / elapsed.as_secs_f64().max(0.001);
+
+    let mut s = format_size(done);
+    if let Some(total) = total {
+        s += " / ";
+        s += &format_size(total);
+    }
+    s += &format!(", {}/s", format_size(rate as u64));
+    if let Some(total) = total {
+        if rate >= 1.0 {
+            let eta = total.saturating_sub(done) as f64 / rate;
+            s += &format!(", ETA {}s", eta.ceil() as u64);
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_progress() {
+        assert_eq!(
+            format_progress(1024 * 1024, Some(4 * 1024 * 1024), Duration::from_secs(2)),
+            "1.0 MiB / 4.0 MiB, 512.0 KiB/s, ETA 6s"
+        );
+        assert_eq!(
+            format_progress(512, None, Duration::from_secs(1)),
+            "512 B, 512 B/s"
+        );
+    }
+}
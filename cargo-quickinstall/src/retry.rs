@@ -0,0 +1,203 @@
+//! Retrying downloads that failed for reasons that might go away by themselves,
+//! like a 502 from GitHub or a connection reset.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    thread,
+    time::Duration,
+};
+
+use crate::{settings::settings, verbosity, CommandFailed, InstallError, Verbosity};
+
+/// How long we wait before the first retry. This doubles after each attempt.
+const BASE_DELAY: Duration = Duration::from_secs(1);
+/// We never wait longer than this between attempts. If the server asks us to
+/// wait longer with `Retry-After`, we give up instead.
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// curl exit codes that mean we didn't get a complete response, but might if we
+/// try again: couldn't resolve the proxy or host, couldn't connect, HTTP/2 framing
+/// error, partial file, timeout, TLS handshake failure, empty reply, send or
+/// receive failure (e.g. connection reset) and HTTP/2 stream error.
+const TRANSIENT_CURL_CODES: &[i32] = &[5, 6, 7, 16, 18, 28, 35, 52, 55, 56, 92];
+
+/// Run `download`, retrying it up to `settings().retries` times if it fails with a
+/// 408, 429, 5xx or connection error. Anything else, including a 404, is returned
+/// straight away.
+pub(crate) fn with_retries<T>(
+    url: &str,
+    download: impl FnMut() -> Result<T, InstallError>,
+) -> Result<T, InstallError> {
+    retry(settings().retries, url, thread::sleep, download)
+}
+
+fn retry<T>(
+    retries: usize,
+    url: &str,
+    mut sleep: impl FnMut(Duration),
+    mut download: impl FnMut() -> Result<T, InstallError>,
+) -> Result<T, InstallError> {
+    let mut attempt = 0;
+    loop {
+        let err = match download() {
+            Err(err) if attempt < retries => err,
+            res => return res,
+        };
+        let Some(delay) = retry_delay(&err, attempt, jitter()) else {
+            return Err(err);
+        };
+        attempt += 1;
+
+        if verbosity() > Verbosity::Quiet {
+            eprintln!(
+                "Downloading {url} failed ({reason}), retrying in {delay:.1}s ({attempt}/{retries})",
+                reason = short_reason(&err),
+                delay = delay.as_secs_f64(),
+            );
+        }
+        sleep(delay);
+    }
+}
+
+/// Returns how long to wait before trying again after the `attempt`th retry
+/// failed with `err`, or `None` if there's no point trying again.
+///
+/// Without a `Retry-After`, we back off exponentially, and wait a random amount
+/// between half and all of that, where `jitter` is between 0 and 1, so that lots
+/// of CI jobs that failed at the same time don't all retry at the same time.
+fn retry_delay(err: &InstallError, attempt: usize, jitter: f64) -> Option<Duration> {
+    let transient = match err {
+        InstallError::Http {
+            status: 408 | 429 | 500..=599,
+            retry_after: Some(retry_after),
+            ..
+        } => return (*retry_after <= MAX_DELAY).then_some(*retry_after),
+        InstallError::Http { status, .. } => matches!(status, 408 | 429 | 500..=599),
+        InstallError::DownloadFailed {
+            err: CommandFailed {
+                code: Some(code), ..
+            },
+            ..
+        } => TRANSIENT_CURL_CODES.contains(code),
+        _ => false,
+    };
+    if !transient {
+        return None;
+    }
+
+    let backoff = BASE_DELAY
+        .checked_mul(1u32 << attempt.min(16))
+        .map_or(MAX_DELAY, |backoff| backoff.min(MAX_DELAY));
+    Some(backoff.mul_f64(0.5 + jitter / 2.0))
+}
+
+/// A random number between 0 and 1, without pulling in `rand`.
+fn jitter() -> f64 {
+    RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64
+}
+
+/// Something that fits on the same line as our retry message.
+fn short_reason(err: &InstallError) -> String {
+    match err {
+        InstallError::Http { status, .. } => format!("HTTP {status}"),
+        InstallError::DownloadFailed { err, .. } => err
+            .stderr
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or("curl failed")
+            .to_string(),
+        err => err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn http(status: u16, retry_after: Option<u64>) -> InstallError {
+        InstallError::Http {
+            status,
+            url: "https://example.com".to_string(),
+            retry_after: retry_after.map(Duration::from_secs),
+        }
+    }
+
+    fn curl_exit(code: i32) -> InstallError {
+        InstallError::DownloadFailed {
+            url: "https://example.com".to_string(),
+            err: CommandFailed {
+                command: "curl https://example.com".to_string(),
+                code: Some(code),
+                stdout: String::new(),
+                stderr: "curl: (56) Recv failure: Connection reset by peer\n".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        // Definitive answers aren't retried.
+        assert_eq!(retry_delay(&http(404, None), 0, 0.0), None);
+        assert_eq!(retry_delay(&http(403, Some(1)), 0, 0.0), None);
+        assert_eq!(retry_delay(&curl_exit(23), 0, 0.0), None);
+
+        assert_eq!(
+            retry_delay(&http(502, None), 0, 0.0),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(
+            retry_delay(&curl_exit(56), 2, 1.0),
+            Some(Duration::from_secs(4))
+        );
+        assert_eq!(retry_delay(&http(503, None), 100, 1.0), Some(MAX_DELAY));
+
+        assert_eq!(
+            retry_delay(&http(429, Some(7)), 0, 0.0),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(retry_delay(&http(429, Some(3600)), 0, 0.0), None);
+    }
+
+    #[test]
+    fn test_retry() {
+        let mut delays = Vec::new();
+        let mut responses = vec![Ok(()), Err(curl_exit(7)), Err(http(503, Some(2)))];
+        let res = retry(
+            3,
+            "https://example.com",
+            |delay| delays.push(delay),
+            || responses.pop().unwrap(),
+        );
+        assert!(res.is_ok());
+        assert_eq!(delays.len(), 2);
+        assert_eq!(delays[0], Duration::from_secs(2));
+
+        let mut attempts = 0;
+        let res: Result<(), _> = retry(
+            2,
+            "https://example.com",
+            |_| (),
+            || {
+                attempts += 1;
+                Err(http(500, None))
+            },
+        );
+        assert_eq!(res.unwrap_err().http_status(), Some(500));
+        assert_eq!(attempts, 3);
+
+        let mut attempts = 0;
+        let res: Result<(), _> = retry(
+            2,
+            "https://example.com",
+            |_| (),
+            || {
+                attempts += 1;
+                Err(http(404, None))
+            },
+        );
+        assert!(res.unwrap_err().is_not_found());
+        assert_eq!(attempts, 1);
+    }
+}
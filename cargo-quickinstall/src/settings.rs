@@ -0,0 +1,68 @@
+use std::{path::PathBuf, sync::OnceLock};
+
+use crate::Verbosity;
+
+/// How many times we retry a download that failed for a reason that might go away
+/// by itself, by default.
+pub const DEFAULT_RETRIES: usize = 3;
+
+/// Settings that affect where and how we make requests.
+///
+/// These are set once by `main()` from the command line, environment and config file, so that
+/// we don't have to thread them through every function that ends up calling curl.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Base url to download pre-built packages from, instead of our GitHub releases.
+    pub mirror: Option<String>,
+    /// Base url of the stats server to report installs to, instead of ours.
+    pub stats_server: Option<String>,
+    /// How many times to retry downloads after a 5xx, a 429 or a connection error.
+    pub retries: usize,
+    /// Sent to github.com, and nowhere else, to get a higher rate limit.
+    pub github_token: Option<String>,
+    /// Proxy to make every request through, instead of the one from `$https_proxy`.
+    pub proxy: Option<String>,
+    /// Comma-separated hosts to connect to directly, instead of the ones from `$no_proxy`.
+    pub no_proxy: Option<String>,
+    /// Extra CA certificates to verify servers with, on top of the system ones.
+    pub ca_cert: Option<PathBuf>,
+    /// How long to wait for a connection, in seconds.
+    pub connect_timeout: Option<u64>,
+    /// How long each request may take altogether, in seconds.
+    pub timeout: Option<u64>,
+    /// Abort downloads that are bigger than this many bytes.
+    pub max_download_size: Option<u64>,
+    /// How much to print, from `-q` and `-v`.
+    pub verbosity: Verbosity,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            mirror: None,
+            stats_server: None,
+            retries: DEFAULT_RETRIES,
+            github_token: None,
+            proxy: None,
+            no_proxy: None,
+            ca_cert: None,
+            connect_timeout: None,
+            timeout: None,
+            max_download_size: None,
+            verbosity: Verbosity::Normal,
+        }
+    }
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Set the settings for the rest of the process.
+///
+/// Only the first call has any effect.
+pub fn init_settings(settings: Settings) {
+    SETTINGS.set(settings).ok();
+}
+
+pub(crate) fn settings() -> &'static Settings {
+    SETTINGS.get_or_init(Settings::default)
+}
@@ -11,3 +11,21 @@ pub fn get_cargo_bin_dir() -> io::Result<PathBuf> {
     cargo_bin_dir.push("bin");
     Ok(cargo_bin_dir)
 }
+
+/// e.g. `512 B` or `1.5 MiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{size:.1} {}", UNITS[unit])
+}
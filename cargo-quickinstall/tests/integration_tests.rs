@@ -1,3 +1,4 @@
+use cargo_quickinstall::install_error::InstallSuccess;
 use cargo_quickinstall::*;
 use std::process;
 
@@ -21,7 +22,7 @@ fn quickinstall_for_cargo_update() {
     };
     let do_not_fallback_on_cargo_install = false;
 
-    let result = install_crate_curl(&crate_details, do_not_fallback_on_cargo_install);
+    let result = install_crate_curl(&crate_details, do_not_fallback_on_cargo_install, false);
 
     assert!(result.is_ok(), "{}", result.err().unwrap());
 
@@ -30,10 +31,29 @@ fn quickinstall_for_cargo_update() {
 
     assert!(cargo_update_path.is_file());
 
-    std::process::Command::new(cargo_update_path)
+    std::process::Command::new(&cargo_update_path)
         .arg("-V")
         .output_checked_status()
         .unwrap();
+
+    // We recorded what we installed, so installing it again is a no-op...
+    let result = install_crate_curl(&crate_details, do_not_fallback_on_cargo_install, false);
+    assert!(
+        matches!(result, Ok(InstallSuccess::AlreadyInstalled)),
+        "{:?}",
+        result
+    );
+
+    // ...and if one of its binaries went missing, we reinstall it over the others
+    // without complaining that they belong to someone else.
+    std::fs::remove_file(&cargo_update_path).unwrap();
+    let result = install_crate_curl(&crate_details, do_not_fallback_on_cargo_install, false);
+    assert!(
+        matches!(result, Ok(InstallSuccess::InstalledFromTarball)),
+        "{:?}",
+        result
+    );
+    assert!(cargo_update_path.is_file());
 }
 
 /// Tests dry run for cargo-update.
@@ -54,6 +74,24 @@ fn do_dry_run_for_cargo_update() {
     assert_eq!(&result[..expected_prefix.len()], expected_prefix);
 }
 
+/// Tests listing the contents of the cargo-update package without installing it.
+#[test]
+fn list_files_for_cargo_update() {
+    let crate_details = CrateDetails {
+        crate_name: "cargo-update".to_string(),
+        version: "18.0.0".to_string(),
+        target: "x86_64-unknown-linux-gnu".to_string(),
+    };
+
+    let listing = list_quickinstall_package(&crate_details).unwrap();
+
+    assert!(
+        listing.contains("cargo-install-update-config"),
+        "{}",
+        listing
+    );
+}
+
 /// Tests dry run for a non-existent package.
 #[test]
 fn do_dry_run_for_nonexistent_package() {
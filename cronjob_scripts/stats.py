@@ -75,6 +75,55 @@ def get_requested_crates(
     return df[["crate", "version"]].unique().to_dicts()  # type: ignore
 
 
+@lru_cache
+def get_build_requests_table(period: str) -> DataFrame:
+    """
+    Explicit build requests from `cargo quickinstall request`, which the stats server records
+    separately from the install counts.
+    """
+    global _influxdb_client
+    if _influxdb_client is None:
+        _influxdb_client = InfluxDBClient3(
+            host=HOST, token=TOKEN, org=ORG, database=DATABASE
+        )
+
+    query = """
+        SELECT DISTINCT crate, target, version
+        FROM "build-requests"
+        WHERE
+            time >= now() - $period::interval and time <= now()
+    """
+
+    try:
+        table: DataFrame = _influxdb_client.query(  # type: ignore
+            query=query,
+            language="sql",
+            query_parameters={
+                "period": period,
+            },
+            mode="polars",
+        )
+    except Exception as e:
+        # The table only exists once someone has requested a build, so don't let that take the
+        # rest of the cronjob down.
+        print(f"Failed to get build requests: {e}")
+        return DataFrame()
+
+    return table
+
+
+def get_build_requests(period: str, target: str | None) -> list[CrateAndVersion]:
+    df = get_build_requests_table(period=period)
+
+    if df.is_empty():
+        return []
+
+    if target is not None:
+        df = df.filter(df["target"] == target)
+
+    return df[["crate", "version"]].unique().to_dicts()  # type: ignore
+
+
 def main():
     table = get_stats(period="1 day")
     for crate in table["crate"].unique():
@@ -18,7 +18,7 @@ from cronjob_scripts.types import CrateAndMaybeVersion, CrateAndVersion, GithubA
 from cronjob_scripts.architectures import get_build_os, get_target_architectures
 from cronjob_scripts.checkout_worktree import checkout_worktree_for_target
 from cronjob_scripts.get_latest_version import CrateVersionDict, get_latest_version
-from cronjob_scripts.stats import get_requested_crates
+from cronjob_scripts.stats import get_build_requests, get_requested_crates
 from cronjob_scripts.crates_io_popular_crates import get_crates_io_popular_crates
 
 MAX_CHECKS_PER_QUEUE = 1000
@@ -42,6 +42,8 @@ def main():
               "not-found" status in the last day (ordered by popularity, including versions.
               WARNING: this is very likely to result in the same crate being built many times in
               parallel if someone runs the cronjob manually on ci/locally)
+            * the list of explicit build requests from `cargo quickinstall request` in the last
+              day (shuffled, including versions)
             * the list of install requests with the "built-from-source" or
               "not-found" status in the last day (shuffled, including versions)
             * the list of install requests with any other status in the last day (shuffled, with
@@ -96,6 +98,15 @@ def main():
         tracking_worktree_path = checkout_worktree_for_target(target)
         excluded = get_excluded(tracking_worktree_path, days=7, max_failures=5)
 
+        # People asked for these explicitly, and there should only be a handful, so we will get
+        # around to them quickly.
+        build_requests = get_build_requests(period="1 day", target=target)
+        build_requests = without_excluded(build_requests, excluded)
+        random.shuffle(build_requests)
+        queues.append(
+            QueueInfo(type="build-requested", target=target, queue=build_requests)
+        )
+
         # This should be a small list of crates, so we should get around to checking everything here
         # pretty often. This doesn't include failures from old clients (via the old stats server)
         # because they don't report status.
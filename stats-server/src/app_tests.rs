@@ -0,0 +1,295 @@
+//! Tests that send requests through the whole `Router`, against an in-memory backend.
+
+use std::{
+    net::SocketAddr,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use tokio::task::JoinHandle;
+use tower::ServiceExt;
+
+use crate::{
+    app,
+    backend::{MemoryBackend, Point},
+    buffer::BufferStats,
+    AppState, BufferConfig, RateLimiter, WriteBuffer,
+};
+
+const VALID_INSTALL: &str =
+    "crate=ripgrep&version=14.1.1&target=x86_64-unknown-linux-gnu&agent=cargo-quickinstall/0.3.0&status=built-from-source";
+
+struct TestApp {
+    router: Router,
+    backend: Arc<MemoryBackend>,
+    buffer_stats: Arc<BufferStats>,
+    flusher: JoinHandle<()>,
+}
+
+impl TestApp {
+    fn new() -> Self {
+        Self::with_rate_limit(100)
+    }
+
+    fn with_rate_limit(max_requests: u32) -> Self {
+        let backend = Arc::new(MemoryBackend::default());
+        let (buffer, flusher) = WriteBuffer::spawn(
+            backend.clone(),
+            BufferConfig {
+                flush_interval: Duration::from_secs(3600),
+                ..BufferConfig::default()
+            },
+        );
+        let buffer_stats = buffer.stats.clone();
+        let router = app(AppState {
+            backend: backend.clone(),
+            buffer,
+            rate_limiter: Arc::new(RateLimiter::new(max_requests, Duration::from_secs(60))),
+            metrics: Arc::default(),
+        });
+
+        Self {
+            router,
+            backend,
+            buffer_stats,
+            flusher,
+        }
+    }
+
+    async fn request(&self, request: Request<Body>) -> (StatusCode, String) {
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    async fn get(&self, uri: &str) -> (StatusCode, String) {
+        self.request(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+    }
+
+    async fn post(&self, uri: &str) -> (StatusCode, String) {
+        self.request(
+            Request::builder()
+                .method(Method::POST)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+    }
+
+    /// Shut down the app, and return everything that ended up in the backend.
+    async fn finish(self) -> Vec<Point> {
+        drop(self.router);
+        self.flusher.await.unwrap();
+        let points = self.backend.points.lock().unwrap();
+        points.clone()
+    }
+}
+
+#[tokio::test]
+async fn test_root() {
+    let app = TestApp::new();
+
+    let (status, body) = app.get("/").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(
+        body.contains("stats server for cargo-quickinstall"),
+        "{body}"
+    );
+}
+
+#[tokio::test]
+async fn test_get_record_install_redirects() {
+    let app = TestApp::new();
+
+    let response = app
+        .router
+        .clone()
+        .oneshot(
+            Request::get(format!("/record-install?{VALID_INSTALL}"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(response.headers()[header::LOCATION], "/");
+    assert!(app.finish().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_record_install() {
+    let app = TestApp::new();
+
+    let (status, body) = app.post(&format!("/record-install?{VALID_INSTALL}")).await;
+    assert_eq!(status, StatusCode::OK);
+    // We don't echo the params back.
+    assert_eq!(body, "Recorded");
+
+    let points = app.finish().await;
+    assert_eq!(points.len(), 1);
+    assert_eq!(points[0].measurement, "counts");
+    assert_eq!(points[0].tags["crate"], "ripgrep");
+    assert_eq!(points[0].tags["status"], "built-from-source");
+    assert_eq!(points[0].tags.len(), 5);
+}
+
+#[tokio::test]
+async fn test_record_install_skips_unknown_params() {
+    let app = TestApp::new();
+
+    let (status, _) = app
+        .post(&format!("/record-install?{VALID_INSTALL}&os=linux&x=y"))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let points = app.finish().await;
+    assert_eq!(points.len(), 1);
+    assert!(!points[0].tags.contains_key("os"));
+    assert!(!points[0].tags.contains_key("x"));
+}
+
+#[tokio::test]
+async fn test_record_install_rejects_invalid_params() {
+    let app = TestApp::new();
+
+    for query in [
+        "crate=ripgrep&version=14.1.1",
+        "crate=ripgrep&version=14.1.1&target=x86_64-unknown-freebsd",
+        "crate=rip%20grep&version=14.1.1&target=x86_64-unknown-linux-gnu",
+        "crate=ripgrep&version=14.1.1&target=x86_64-unknown-linux-gnu&status=made-up",
+    ] {
+        let (status, _) = app.post(&format!("/record-install?{query}")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{query}");
+    }
+
+    assert!(app.finish().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_request_build() {
+    let app = TestApp::new();
+
+    let (status, _) = app
+        .post("/request-build?crate=ripgrep&version=14.1.1&target=aarch64-apple-darwin&status=built-from-source")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let points = app.finish().await;
+    assert_eq!(points.len(), 1);
+    assert_eq!(points[0].measurement, "build-requests");
+    // Build requests don't have a status.
+    assert!(!points[0].tags.contains_key("status"));
+}
+
+#[tokio::test]
+async fn test_rate_limit() {
+    let app = TestApp::with_rate_limit(2);
+    let post_from = |ip: [u8; 4]| {
+        Request::builder()
+            .method(Method::POST)
+            .uri(format!("/record-install?{VALID_INSTALL}"))
+            // Without `--trust-proxy-header`, this should be ignored.
+            .header("fly-client-ip", "192.0.2.100")
+            .extension(ConnectInfo(SocketAddr::from((ip, 1234))))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    assert_eq!(
+        app.request(post_from([192, 0, 2, 1])).await.0,
+        StatusCode::OK
+    );
+    assert_eq!(
+        app.request(post_from([192, 0, 2, 1])).await.0,
+        StatusCode::OK
+    );
+    assert_eq!(
+        app.request(post_from([192, 0, 2, 1])).await.0,
+        StatusCode::TOO_MANY_REQUESTS
+    );
+    assert_eq!(
+        app.request(post_from([192, 0, 2, 2])).await.0,
+        StatusCode::OK
+    );
+
+    // `/popular` counts towards the same limit.
+    let popular = Request::builder()
+        .uri("/popular")
+        .extension(ConnectInfo(SocketAddr::from(([192, 0, 2, 1], 1234))))
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(app.request(popular).await.0, StatusCode::TOO_MANY_REQUESTS);
+
+    let (_, metrics) = app.get("/metrics").await;
+    for line in [
+        r#"stats_http_requests_total{route="/record-install",code="200"} 3"#,
+        r#"stats_http_requests_total{route="/record-install",code="429"} 1"#,
+    ] {
+        assert!(metrics.lines().any(|l| l == line), "{line}\n{metrics}");
+    }
+
+    assert_eq!(app.finish().await.len(), 3);
+}
+
+#[tokio::test]
+async fn test_popular() {
+    let app = TestApp::new();
+    app.backend.points.lock().unwrap().extend([
+        Point::new("counts")
+            .tag("crate", "ripgrep")
+            .tag("version", "14.1.1")
+            .tag("target", "x86_64-unknown-linux-gnu")
+            .tag("status", "built-from-source"),
+        Point::new("counts")
+            .tag("crate", "bat")
+            .tag("version", "0.24.0")
+            .tag("target", "aarch64-apple-darwin"),
+    ]);
+
+    let (status, body) = app.get("/popular?target=x86_64-unknown-linux-gnu").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        r#"[{"crate":"ripgrep","version":"14.1.1","count":1,"statuses":{"built-from-source":1}}]"#
+    );
+
+    let (status, _) = app.get("/popular?since=forever").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.get("/popular?since=30d").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.get("/popular?since=100000w").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.get("/popular?limit=-1").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_backend_down() {
+    let app = TestApp::new();
+    app.backend.failing.store(true, Ordering::Relaxed);
+
+    assert_eq!(app.get("/healthz").await.0, StatusCode::OK);
+    assert_eq!(app.get("/readyz").await.0, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(app.get("/popular").await.0, StatusCode::SERVICE_UNAVAILABLE);
+
+    // Installs are buffered, so we only find out that they were dropped from the metrics.
+    let (status, _) = app.post(&format!("/record-install?{VALID_INSTALL}")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let buffer_stats = app.buffer_stats.clone();
+    assert!(app.finish().await.is_empty());
+    assert_eq!(buffer_stats.dropped.load(Ordering::Relaxed), 1);
+    assert_eq!(buffer_stats.write_errors.load(Ordering::Relaxed), 1);
+}
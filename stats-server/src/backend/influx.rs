@@ -0,0 +1,101 @@
+use std::time::UNIX_EPOCH;
+
+use influxrs::{InfluxClient, Measurement, Query};
+
+use super::{
+    aggregate_popular, Backend, BackendError, BoxFuture, Point, PopularCrate, PopularQuery,
+};
+
+pub struct InfluxBackend {
+    client: InfluxClient,
+    bucket: String,
+}
+
+impl InfluxBackend {
+    pub fn new(
+        url: String,
+        token: String,
+        org: String,
+        bucket: String,
+    ) -> Result<Self, BackendError> {
+        let client = InfluxClient::builder(url, token, org)
+            .build()
+            .map_err(|err| BackendError(format!("Invalid influxdb settings: {err:?}")))?;
+        Ok(Self { client, bucket })
+    }
+}
+
+fn to_measurement(point: &Point) -> Result<Measurement, BackendError> {
+    let mut builder = Measurement::builder(point.measurement).field("count", 1);
+    for (tag, value) in &point.tags {
+        builder = builder.tag(tag, value.as_str());
+    }
+    if let Ok(since_epoch) = point.time.duration_since(UNIX_EPOCH) {
+        builder = builder.timestamp_nanos(since_epoch.as_nanos() as i128);
+    }
+    builder
+        .build()
+        .map_err(|err| BackendError(format!("Invalid measurement: {err:?}")))
+}
+
+impl Backend for InfluxBackend {
+    fn write<'a>(&'a self, points: &'a [Point]) -> BoxFuture<'a, Result<(), BackendError>> {
+        Box::pin(async move {
+            let measurements = points
+                .iter()
+                .map(to_measurement)
+                .collect::<Result<Vec<_>, _>>()?;
+
+            self.client
+                .write(&self.bucket, &measurements)
+                .await
+                .map_err(|err| BackendError(format!("Failed to write to influxdb: {err}")))
+        })
+    }
+
+    fn ping(&self) -> BoxFuture<'_, Result<(), BackendError>> {
+        Box::pin(async move {
+            // Listing buckets is cheap, and also checks that our token is still valid.
+            self.client
+                .query(Query::new("buckets()").then("limit(n: 1)"))
+                .await
+                .map(|_| ())
+                .map_err(|err| BackendError(format!("Failed to query influxdb: {err}")))
+        })
+    }
+
+    fn popular<'a>(
+        &'a self,
+        query: &'a PopularQuery,
+    ) -> BoxFuture<'a, Result<Vec<PopularCrate>, BackendError>> {
+        Box::pin(async move {
+            // The handler has already checked that the target doesn't contain any quotes.
+            let mut flux = Query::new(format!(r#"from(bucket: "{}")"#, self.bucket))
+                .then(format!("range(start: -{}s)", query.since.as_secs()))
+                .then(r#"filter(fn: (r) => r._measurement == "counts" and r._field == "count")"#);
+            if let Some(target) = &query.target {
+                flux = flux.then(format!(r#"filter(fn: (r) => r.target == "{target}")"#));
+            }
+            flux = flux
+                .then(r#"group(columns: ["crate", "version", "status"])"#)
+                .then("sum()");
+
+            let records = self
+                .client
+                .query(flux)
+                .await
+                .map_err(|err| BackendError(format!("Failed to query influxdb: {err}")))?;
+
+            let rows = records.into_iter().filter_map(|mut record| {
+                Some((
+                    record.remove("crate")?,
+                    record.remove("version")?,
+                    record.remove("status"),
+                    record.get("_value")?.parse().ok()?,
+                ))
+            });
+
+            Ok(aggregate_popular(rows, query.limit))
+        })
+    }
+}
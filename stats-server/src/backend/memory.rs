@@ -0,0 +1,74 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::SystemTime,
+};
+
+use super::{
+    aggregate_popular, Backend, BackendError, BoxFuture, Point, PopularCrate, PopularQuery,
+};
+
+/// Keeps points in memory, for tests.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    pub points: Mutex<Vec<Point>>,
+    /// The size of each batch that we were asked to write.
+    pub batches: Mutex<Vec<usize>>,
+    /// Make every call fail, to test what happens when the backend is down.
+    pub failing: AtomicBool,
+}
+
+impl MemoryBackend {
+    fn check_failing(&self) -> Result<(), BackendError> {
+        if self.failing.load(Ordering::Relaxed) {
+            return Err(BackendError("The backend is down".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn write<'a>(&'a self, points: &'a [Point]) -> BoxFuture<'a, Result<(), BackendError>> {
+        Box::pin(async move {
+            self.check_failing()?;
+            self.batches.lock().unwrap().push(points.len());
+            self.points.lock().unwrap().extend_from_slice(points);
+            Ok(())
+        })
+    }
+
+    fn ping(&self) -> BoxFuture<'_, Result<(), BackendError>> {
+        Box::pin(async move { self.check_failing() })
+    }
+
+    fn popular<'a>(
+        &'a self,
+        query: &'a PopularQuery,
+    ) -> BoxFuture<'a, Result<Vec<PopularCrate>, BackendError>> {
+        Box::pin(async move {
+            self.check_failing()?;
+            let since = SystemTime::now() - query.since;
+            let points = self.points.lock().unwrap();
+            let rows = points
+                .iter()
+                .filter(|point| point.measurement == "counts" && point.time >= since)
+                .filter(|point| {
+                    query
+                        .target
+                        .as_ref()
+                        .is_none_or(|target| point.tags.get("target") == Some(target))
+                })
+                .filter_map(|point| {
+                    Some((
+                        point.tags.get("crate")?.clone(),
+                        point.tags.get("version")?.clone(),
+                        point.tags.get("status").cloned(),
+                        1,
+                    ))
+                });
+            Ok(aggregate_popular(rows, query.limit))
+        })
+    }
+}
@@ -0,0 +1,177 @@
+//! Where we store the stats.
+//!
+//! In production this is InfluxDB, but you can use a local SQLite database to run
+//! the server on your laptop, or in tests.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use serde::Serialize;
+
+use crate::config::BackendConfig;
+
+mod influx;
+#[cfg(test)]
+mod memory;
+mod sqlite;
+
+pub use influx::InfluxBackend;
+#[cfg(test)]
+pub use memory::MemoryBackend;
+pub use sqlite::SqliteBackend;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A single event, like an install or a build request, which counts as 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Point {
+    pub measurement: &'static str,
+    pub tags: BTreeMap<String, String>,
+    pub time: SystemTime,
+}
+
+impl Point {
+    pub fn new(measurement: &'static str) -> Self {
+        Self {
+            measurement,
+            tags: BTreeMap::new(),
+            time: SystemTime::now(),
+        }
+    }
+
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.insert(key.into(), value.into());
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct BackendError(pub String);
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+/// Which installs to count in [`Backend::popular`].
+#[derive(Debug, Clone)]
+pub struct PopularQuery {
+    pub target: Option<String>,
+    /// Only count installs from the last `since`.
+    pub since: Duration,
+    pub limit: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PopularCrate {
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    pub version: String,
+    pub count: u64,
+    /// How many of the installs ended with each status, like "built-from-source".
+    pub statuses: BTreeMap<String, u64>,
+}
+
+pub trait Backend: Send + Sync {
+    fn write<'a>(&'a self, points: &'a [Point]) -> BoxFuture<'a, Result<(), BackendError>>;
+
+    /// Check that we can talk to the backend, for the readiness probe.
+    fn ping(&self) -> BoxFuture<'_, Result<(), BackendError>>;
+
+    /// The most installed crate versions, most popular first.
+    fn popular<'a>(
+        &'a self,
+        query: &'a PopularQuery,
+    ) -> BoxFuture<'a, Result<Vec<PopularCrate>, BackendError>>;
+}
+
+/// Combine (crate, version, status, count) rows into the `limit` most popular crate versions.
+///
+/// Installs from old clients don't have a status, so they only show up in the total count.
+fn aggregate_popular(
+    rows: impl IntoIterator<Item = (String, String, Option<String>, u64)>,
+    limit: usize,
+) -> Vec<PopularCrate> {
+    let mut crates: HashMap<(String, String), PopularCrate> = HashMap::new();
+
+    for (crate_name, version, status, count) in rows {
+        let entry = crates
+            .entry((crate_name.clone(), version.clone()))
+            .or_insert_with(|| PopularCrate {
+                crate_name,
+                version,
+                count: 0,
+                statuses: BTreeMap::new(),
+            });
+        entry.count += count;
+        if let Some(status) = status.filter(|status| !status.is_empty()) {
+            *entry.statuses.entry(status).or_default() += count;
+        }
+    }
+
+    let mut crates = crates.into_values().collect::<Vec<_>>();
+    crates.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.crate_name.cmp(&b.crate_name))
+            .then_with(|| a.version.cmp(&b.version))
+    });
+    crates.truncate(limit);
+    crates
+}
+
+pub fn open(config: &BackendConfig) -> Result<Arc<dyn Backend>, BackendError> {
+    match config.clone() {
+        BackendConfig::Influx {
+            url,
+            token,
+            org,
+            bucket,
+        } => Ok(Arc::new(InfluxBackend::new(url, token, org, bucket)?)),
+        BackendConfig::Sqlite { path } => Ok(Arc::new(SqliteBackend::open(path)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aggregate_popular() {
+        let row = |crate_name: &str, version: &str, status: Option<&str>, count| {
+            (
+                crate_name.to_string(),
+                version.to_string(),
+                status.map(str::to_string),
+                count,
+            )
+        };
+
+        let popular = aggregate_popular(
+            [
+                row("ripgrep", "14.1.1", Some("installed-from-tarball"), 5),
+                row("ripgrep", "14.1.1", Some("built-from-source"), 2),
+                row("ripgrep", "14.1.1", None, 1),
+                row("bat", "0.24.0", Some("built-from-source"), 3),
+                row("fd-find", "10.2.0", Some("built-from-source"), 1),
+            ],
+            2,
+        );
+
+        assert_eq!(popular.len(), 2);
+        assert_eq!(popular[0].crate_name, "ripgrep");
+        assert_eq!(popular[0].count, 8);
+        assert_eq!(popular[0].statuses["built-from-source"], 2);
+        assert_eq!(popular[0].statuses.len(), 2);
+        assert_eq!(popular[1].crate_name, "bat");
+    }
+}
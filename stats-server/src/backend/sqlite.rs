@@ -0,0 +1,222 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::Connection;
+
+use super::{
+    aggregate_popular, Backend, BackendError, BoxFuture, Point, PopularCrate, PopularQuery,
+};
+
+/// Stores points in a local SQLite database, one row per point.
+pub struct SqliteBackend {
+    conn: Arc<Mutex<Connection>>,
+}
+
+fn sqlite_error(err: rusqlite::Error) -> BackendError {
+    BackendError(format!("sqlite error: {err}"))
+}
+
+impl SqliteBackend {
+    /// Open (or create) the database at `path`. Use `:memory:` for a throwaway database.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BackendError> {
+        let path = path.as_ref();
+        let conn = Connection::open(path).map_err(|err| {
+            BackendError(format!(
+                "Failed to open sqlite database {}: {err}",
+                path.display()
+            ))
+        })?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS points (
+                time_ms INTEGER NOT NULL,
+                measurement TEXT NOT NULL,
+                crate TEXT,
+                version TEXT,
+                target TEXT,
+                agent TEXT,
+                status TEXT
+            );
+            CREATE INDEX IF NOT EXISTS points_measurement_time ON points (measurement, time_ms);",
+        )
+        .map_err(sqlite_error)?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+}
+
+impl Backend for SqliteBackend {
+    fn write<'a>(&'a self, points: &'a [Point]) -> BoxFuture<'a, Result<(), BackendError>> {
+        let conn = self.conn.clone();
+        let points = points.to_vec();
+
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || {
+                let mut conn = conn.lock().unwrap();
+                let tx = conn.transaction().map_err(sqlite_error)?;
+                {
+                    let mut stmt = tx
+                        .prepare_cached(
+                            "INSERT INTO points (time_ms, measurement, crate, version, target, agent, status)
+                            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                        )
+                        .map_err(sqlite_error)?;
+                    for point in &points {
+                        let time_ms = point
+                            .time
+                            .duration_since(UNIX_EPOCH)
+                            .map(|d| d.as_millis() as i64)
+                            .unwrap_or_default();
+                        let tag = |key: &str| point.tags.get(key).map(String::as_str);
+                        stmt.execute((
+                            time_ms,
+                            point.measurement,
+                            tag("crate"),
+                            tag("version"),
+                            tag("target"),
+                            tag("agent"),
+                            tag("status"),
+                        ))
+                        .map_err(sqlite_error)?;
+                    }
+                }
+                tx.commit().map_err(sqlite_error)
+            })
+            .await
+            .map_err(|err| BackendError(format!("sqlite writer panicked: {err}")))?
+        })
+    }
+
+    fn ping(&self) -> BoxFuture<'_, Result<(), BackendError>> {
+        let conn = self.conn.clone();
+
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || {
+                conn.lock()
+                    .unwrap()
+                    .query_row("SELECT 1", (), |_| Ok(()))
+                    .map_err(sqlite_error)
+            })
+            .await
+            .map_err(|err| BackendError(format!("sqlite ping panicked: {err}")))?
+        })
+    }
+
+    fn popular<'a>(
+        &'a self,
+        query: &'a PopularQuery,
+    ) -> BoxFuture<'a, Result<Vec<PopularCrate>, BackendError>> {
+        let conn = self.conn.clone();
+        let query = query.clone();
+
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || {
+                let since_ms = SystemTime::now()
+                    .checked_sub(query.since)
+                    .and_then(|since| since.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_millis() as i64)
+                    .unwrap_or_default();
+
+                let conn = conn.lock().unwrap();
+                let mut stmt = conn
+                    .prepare_cached(
+                        "SELECT crate, version, status, COUNT(*) FROM points
+                        WHERE measurement = 'counts'
+                            AND time_ms >= ?1
+                            AND (?2 IS NULL OR target = ?2)
+                            AND crate IS NOT NULL
+                            AND version IS NOT NULL
+                        GROUP BY crate, version, status",
+                    )
+                    .map_err(sqlite_error)?;
+                let rows = stmt
+                    .query_map((since_ms, query.target.as_deref()), |row| {
+                        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+                    })
+                    .map_err(sqlite_error)?
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(sqlite_error)?;
+
+                Ok(aggregate_popular(rows, query.limit))
+            })
+            .await
+            .map_err(|err| BackendError(format!("sqlite reader panicked: {err}")))?
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_write() {
+        let backend = SqliteBackend::open(":memory:").unwrap();
+
+        backend
+            .write(&[
+                Point::new("counts").tag("crate", "ripgrep"),
+                Point::new("startups"),
+            ])
+            .await
+            .unwrap();
+
+        let count: i64 = backend
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM points WHERE crate = 'ripgrep'",
+                (),
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_popular() {
+        let backend = SqliteBackend::open(":memory:").unwrap();
+
+        let install = |crate_name: &str, target: &str, status: &str| {
+            Point::new("counts")
+                .tag("crate", crate_name)
+                .tag("version", "1.0.0")
+                .tag("target", target)
+                .tag("status", status)
+        };
+        backend
+            .write(&[
+                install("ripgrep", "x86_64-unknown-linux-gnu", "built-from-source"),
+                install(
+                    "ripgrep",
+                    "x86_64-unknown-linux-gnu",
+                    "installed-from-tarball",
+                ),
+                install("bat", "x86_64-unknown-linux-gnu", "built-from-source"),
+                install("bat", "aarch64-apple-darwin", "built-from-source"),
+                install("bat", "aarch64-apple-darwin", "built-from-source"),
+            ])
+            .await
+            .unwrap();
+
+        let popular = backend
+            .popular(&PopularQuery {
+                target: Some("x86_64-unknown-linux-gnu".to_string()),
+                since: std::time::Duration::from_secs(60),
+                limit: 10,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(popular.len(), 2);
+        assert_eq!(popular[0].crate_name, "ripgrep");
+        assert_eq!(popular[0].count, 2);
+        assert_eq!(popular[1].crate_name, "bat");
+        assert_eq!(popular[1].count, 1);
+    }
+}
@@ -0,0 +1,179 @@
+//! Buffers points in memory and writes them to the backend in batches, so that a burst of
+//! installs from CI doesn't turn into a burst of backend writes.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    sync::mpsc::{self, error::TrySendError},
+    task::JoinHandle,
+    time::MissedTickBehavior,
+};
+
+use crate::{
+    backend::{Backend, Point},
+    log::warning,
+    metrics::Histogram,
+};
+
+#[derive(Debug, Clone)]
+pub struct BufferConfig {
+    /// How many points we hold before dropping new ones.
+    pub capacity: usize,
+    /// Flush as soon as we have this many points.
+    pub batch_size: usize,
+    /// Flush whatever we have at least this often.
+    pub flush_interval: Duration,
+}
+
+impl Default for BufferConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 10_000,
+            batch_size: 500,
+            flush_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BufferStats {
+    /// Points that have been accepted but not yet written.
+    pub queue_depth: AtomicUsize,
+    /// Points that were dropped because the buffer was full, or because writing them failed.
+    pub dropped: AtomicU64,
+    /// Points that were written to the backend.
+    pub written: AtomicU64,
+    /// Batches that we failed to write.
+    pub write_errors: AtomicU64,
+    pub write_latency: Histogram,
+}
+
+/// The sending half of the buffer. Once every clone has been dropped, the flusher
+/// writes whatever is left and exits.
+#[derive(Clone)]
+pub struct WriteBuffer {
+    tx: mpsc::Sender<Point>,
+    pub stats: Arc<BufferStats>,
+}
+
+#[derive(Debug)]
+pub struct BufferFull;
+
+impl WriteBuffer {
+    /// Start the flusher task, which runs until every `WriteBuffer` has been dropped.
+    pub fn spawn(backend: Arc<dyn Backend>, config: BufferConfig) -> (Self, JoinHandle<()>) {
+        let (tx, rx) = mpsc::channel(config.capacity);
+        let stats = Arc::new(BufferStats::default());
+        let flusher = tokio::spawn(flush_loop(backend, config, rx, stats.clone()));
+
+        (Self { tx, stats }, flusher)
+    }
+
+    pub fn push(&self, point: Point) -> Result<(), BufferFull> {
+        // Count the point before sending it, so that the flusher can't take it off
+        // the queue depth before we've added it.
+        self.stats.queue_depth.fetch_add(1, Ordering::Relaxed);
+        match self.tx.try_send(point) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_) | TrySendError::Closed(_)) => {
+                self.stats.queue_depth.fetch_sub(1, Ordering::Relaxed);
+                self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+                Err(BufferFull)
+            }
+        }
+    }
+}
+
+async fn flush_loop(
+    backend: Arc<dyn Backend>,
+    config: BufferConfig,
+    mut rx: mpsc::Receiver<Point>,
+    stats: Arc<BufferStats>,
+) {
+    let mut batch = Vec::with_capacity(config.batch_size);
+    let mut interval = tokio::time::interval(config.flush_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            point = rx.recv() => match point {
+                Some(point) => {
+                    batch.push(point);
+                    if batch.len() >= config.batch_size {
+                        flush(&*backend, &mut batch, &stats).await;
+                    }
+                }
+                None => {
+                    flush(&*backend, &mut batch, &stats).await;
+                    return;
+                }
+            },
+            _ = interval.tick() => flush(&*backend, &mut batch, &stats).await,
+        }
+    }
+}
+
+async fn flush(backend: &dyn Backend, batch: &mut Vec<Point>, stats: &BufferStats) {
+    if batch.is_empty() {
+        return;
+    }
+    let len = batch.len();
+
+    let start = Instant::now();
+    let result = backend.write(batch).await;
+    stats.write_latency.observe(start.elapsed());
+
+    match result {
+        Ok(()) => {
+            stats.written.fetch_add(len as u64, Ordering::Relaxed);
+        }
+        Err(err) => {
+            warning!("Dropping {len} points after failing to write them: {err}");
+            stats.dropped.fetch_add(len as u64, Ordering::Relaxed);
+            stats.write_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    stats.queue_depth.fetch_sub(len, Ordering::Relaxed);
+    batch.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+
+    #[tokio::test]
+    async fn test_flushes_in_batches_and_drops_when_full() {
+        let backend = Arc::new(MemoryBackend::default());
+        let (buffer, flusher) = WriteBuffer::spawn(
+            backend.clone(),
+            BufferConfig {
+                capacity: 3,
+                batch_size: 2,
+                flush_interval: Duration::from_secs(3600),
+            },
+        );
+
+        // The flusher doesn't get to run until we yield, so the fourth point doesn't fit.
+        for _ in 0..3 {
+            buffer.push(Point::new("counts")).unwrap();
+        }
+        assert!(buffer.push(Point::new("counts")).is_err());
+        assert_eq!(buffer.stats.queue_depth.load(Ordering::Relaxed), 3);
+
+        let stats = buffer.stats.clone();
+        drop(buffer);
+        flusher.await.unwrap();
+
+        assert_eq!(*backend.batches.lock().unwrap(), [2, 1]);
+        assert_eq!(stats.queue_depth.load(Ordering::Relaxed), 0);
+        assert_eq!(stats.written.load(Ordering::Relaxed), 3);
+        assert_eq!(stats.dropped.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,284 @@
+//! Command line options, each of which falls back to an environment variable so that
+//! fly.toml can keep configuring the server with `[env]`.
+
+use std::{ffi::OsString, fmt::Display, net::SocketAddr, path::PathBuf, str::FromStr};
+
+use crate::log::LogLevel;
+
+pub const HELP: &str = "USAGE:
+    cargo-quickinstall-stats-server [OPTIONS]
+
+OPTIONS:
+        --bind <ADDR>               Address to listen on [env: BIND_ADDR]
+                                    Defaults to [::]:$PORT, or [::]:8080 if $PORT isn't set.
+        --backend <BACKEND>         Where to store stats, `influx` or `sqlite` [env: STATS_BACKEND]
+                                    Defaults to influx.
+        --sqlite-path <PATH>        SQLite database to use with `--backend sqlite` [env: SQLITE_PATH]
+                                    Defaults to stats.sqlite3.
+        --influx-url <URL>          InfluxDB url [env: INFLUX_URL]
+        --influx-org <ORG>          InfluxDB organisation [env: INFLUX_ORG]
+        --influx-bucket <BUCKET>    InfluxDB bucket [env: INFLUX_BUCKET]
+                                    The InfluxDB token can only be set with $INFLUX_TOKEN, so that
+                                    it doesn't show up in `ps`.
+        --log-level <LEVEL>         One of error, warn, info or debug [env: LOG_LEVEL]
+                                    Defaults to info.
+        --log-client-ips            Include client ips in the request logs [env: LOG_CLIENT_IPS]
+                                    They are left out by default, for privacy.
+        --trust-proxy-header        Take client ips from the `Fly-Client-IP` header that fly.io's
+                                    proxy adds, rather than from the connection
+                                    [env: TRUST_PROXY_HEADER]
+                                    Only use this behind that proxy, since anyone else can set it.
+    -h, --help                      Print help information
+";
+
+const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_SQLITE_PATH: &str = "stats.sqlite3";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackendConfig {
+    Influx {
+        url: String,
+        token: String,
+        org: String,
+        bucket: String,
+    },
+    Sqlite {
+        path: PathBuf,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub bind: SocketAddr,
+    pub backend: BackendConfig,
+    pub log_level: LogLevel,
+    pub log_client_ips: bool,
+    pub trust_proxy_header: bool,
+}
+
+/// What the user asked for on the command line.
+pub enum Command {
+    Help,
+    Run(Config),
+}
+
+/// Looks up an environment variable.
+type Env<'a> = &'a dyn Fn(&str) -> Option<OsString>;
+
+impl Command {
+    pub fn from_args_and_env() -> Result<Self, String> {
+        Self::parse(pico_args::Arguments::from_env(), &|name| {
+            std::env::var_os(name)
+        })
+    }
+
+    fn parse(mut args: pico_args::Arguments, env: Env<'_>) -> Result<Self, String> {
+        if args.contains(["-h", "--help"]) {
+            return Ok(Command::Help);
+        }
+
+        let bind = match option(&mut args, "--bind", env, "BIND_ADDR")? {
+            Some(bind) => bind,
+            None => {
+                let port = env_value(env, "PORT")?.unwrap_or(DEFAULT_PORT);
+                // ipv6 + ipv4 any addr
+                SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 0], port))
+            }
+        };
+
+        let backend_name: String = option(&mut args, "--backend", env, "STATS_BACKEND")?
+            .unwrap_or_else(|| "influx".into());
+        let sqlite_path: Option<PathBuf> = option(&mut args, "--sqlite-path", env, "SQLITE_PATH")?;
+        let influx_url: Option<String> = option(&mut args, "--influx-url", env, "INFLUX_URL")?;
+        let influx_org: Option<String> = option(&mut args, "--influx-org", env, "INFLUX_ORG")?;
+        let influx_bucket: Option<String> =
+            option(&mut args, "--influx-bucket", env, "INFLUX_BUCKET")?;
+        let log_level = option(&mut args, "--log-level", env, "LOG_LEVEL")?.unwrap_or_default();
+        let log_client_ips =
+            args.contains("--log-client-ips") || env_flag(env, "LOG_CLIENT_IPS")?.unwrap_or(false);
+        let trust_proxy_header = args.contains("--trust-proxy-header")
+            || env_flag(env, "TRUST_PROXY_HEADER")?.unwrap_or(false);
+
+        let remaining = args.finish();
+        if !remaining.is_empty() {
+            return Err(format!("unexpected arguments: {remaining:?}"));
+        }
+
+        let backend = match backend_name.as_str() {
+            "influx" => {
+                let required = |value: Option<String>, flag: &str, env_name: &str| {
+                    value.ok_or_else(|| format!("{flag} or ${env_name} must be set to use influx"))
+                };
+                BackendConfig::Influx {
+                    url: required(influx_url, "--influx-url", "INFLUX_URL")?,
+                    token: env_value(env, "INFLUX_TOKEN")?
+                        .ok_or_else(|| "$INFLUX_TOKEN must be set to use influx".to_string())?,
+                    org: required(influx_org, "--influx-org", "INFLUX_ORG")?,
+                    bucket: required(influx_bucket, "--influx-bucket", "INFLUX_BUCKET")?,
+                }
+            }
+            "sqlite" => BackendConfig::Sqlite {
+                path: sqlite_path.unwrap_or_else(|| DEFAULT_SQLITE_PATH.into()),
+            },
+            other => {
+                return Err(format!(
+                    "unknown backend `{other}`, expected `influx` or `sqlite`"
+                ))
+            }
+        };
+
+        Ok(Command::Run(Config {
+            bind,
+            backend,
+            log_level,
+            log_client_ips,
+            trust_proxy_header,
+        }))
+    }
+}
+
+/// Parse the environment variable `name`. Empty values are treated as if it was not set.
+fn env_value<T>(env: Env<'_>, name: &str) -> Result<Option<T>, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = match env(name) {
+        Some(value) if !value.is_empty() => value,
+        _ => return Ok(None),
+    };
+    let value = value
+        .into_string()
+        .map_err(|_| format!("environment variable {name} is not valid UTF-8"))?;
+
+    value
+        .parse()
+        .map(Some)
+        .map_err(|err| format!("failed to parse environment variable {name}='{value}': {err}"))
+}
+
+/// Like [`env_value`], but for environment variables that stand in for `--flags`.
+fn env_flag(env: Env<'_>, name: &str) -> Result<Option<bool>, String> {
+    let value = env_value::<String>(env, name)?;
+
+    match value.as_deref() {
+        None => Ok(None),
+        Some("1" | "true" | "yes" | "on") => Ok(Some(true)),
+        Some("0" | "false" | "no" | "off") => Ok(Some(false)),
+        Some(value) => Err(format!(
+            "failed to parse environment variable {name}='{value}': expected one of 1, true, yes, on, 0, false, no or off"
+        )),
+    }
+}
+
+/// Parse `--flag <value>`, falling back to the environment variable `env_name`.
+fn option<T>(
+    args: &mut pico_args::Arguments,
+    flag: &'static str,
+    env: Env<'_>,
+    env_name: &str,
+) -> Result<Option<T>, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match args
+        .opt_value_from_str(flag)
+        .map_err(|err| format!("{flag}: {err}"))?
+    {
+        Some(value) => Ok(Some(value)),
+        None => env_value(env, env_name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn parse(args: &[&str], env: &[(&str, &str)]) -> Result<Config, String> {
+        let args = pico_args::Arguments::from_vec(args.iter().map(OsString::from).collect());
+        let env: HashMap<_, _> = env.iter().copied().collect();
+        match Command::parse(args, &|name| env.get(name).map(OsString::from))? {
+            Command::Help => panic!("unexpected --help"),
+            Command::Run(config) => Ok(config),
+        }
+    }
+
+    const INFLUX_ENV: &[(&str, &str)] = &[
+        ("PORT", "9000"),
+        ("INFLUX_URL", "https://influx.example.com"),
+        ("INFLUX_TOKEN", "secret"),
+        ("INFLUX_ORG", "cargo-bins"),
+        ("INFLUX_BUCKET", "cargo-quickinstall"),
+    ];
+
+    #[test]
+    fn test_config_from_env() {
+        let config = parse(&[], INFLUX_ENV).unwrap();
+
+        assert_eq!(config.bind, "[::]:9000".parse().unwrap());
+        assert_eq!(config.log_level, LogLevel::Info);
+        assert!(!config.log_client_ips);
+        assert!(!config.trust_proxy_header);
+        assert_eq!(
+            config.backend,
+            BackendConfig::Influx {
+                url: "https://influx.example.com".to_string(),
+                token: "secret".to_string(),
+                org: "cargo-bins".to_string(),
+                bucket: "cargo-quickinstall".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_args_override_env() {
+        let config = parse(
+            &[
+                "--bind",
+                "127.0.0.1:8081",
+                "--backend",
+                "sqlite",
+                "--log-level",
+                "debug",
+                "--trust-proxy-header",
+            ],
+            &[
+                ("STATS_BACKEND", "influx"),
+                ("SQLITE_PATH", "/data/stats.db"),
+                ("LOG_CLIENT_IPS", "1"),
+                ("TRUST_PROXY_HEADER", "0"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(config.bind, "127.0.0.1:8081".parse().unwrap());
+        assert_eq!(config.log_level, LogLevel::Debug);
+        assert!(config.log_client_ips);
+        assert!(config.trust_proxy_header);
+        assert_eq!(
+            config.backend,
+            BackendConfig::Sqlite {
+                path: "/data/stats.db".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_config_errors() {
+        assert_eq!(
+            parse(&[], &INFLUX_ENV[..4]).unwrap_err(),
+            "--influx-bucket or $INFLUX_BUCKET must be set to use influx"
+        );
+        assert_eq!(
+            parse(&[], &[("INFLUX_URL", "https://influx.example.com")]).unwrap_err(),
+            "$INFLUX_TOKEN must be set to use influx"
+        );
+        assert!(parse(&["--backend", "postgres"], &[]).is_err());
+        assert!(parse(&["--bind", "localhost"], &[]).is_err());
+        assert!(parse(&["--backend", "sqlite", "--log-level", "loud"], &[]).is_err());
+        assert!(parse(&["--backend", "sqlite", "--frobnicate"], &[]).is_err());
+    }
+}
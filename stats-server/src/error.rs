@@ -0,0 +1,47 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+
+use crate::{backend::BackendError, log::error};
+
+/// Everything that can go wrong in a handler, and the response that the client gets for it.
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(String),
+    TooManyRequests,
+    /// We can't keep up with writing to the backend, so we are dropping points.
+    BufferFull,
+    Backend(BackendError),
+}
+
+impl From<BackendError> for AppError {
+    fn from(err: BackendError) -> Self {
+        AppError::Backend(err)
+    }
+}
+
+impl AppError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            AppError::BufferFull | AppError::Backend(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        match self {
+            AppError::BadRequest(msg) => (status, msg).into_response(),
+            AppError::TooManyRequests => (status, "Too many requests").into_response(),
+            AppError::BufferFull => (status, "Too busy to record stats").into_response(),
+            AppError::Backend(err) => {
+                error!("Backend error: {err}");
+                (status, "Failed to talk to the stats backend").into_response()
+            }
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! A minimal leveled logger that writes JSON lines to stdout, where fly.io picks them up.
+//!
+//! Client ips are only logged if `--log-client-ips` is set, and we never log query
+//! params, so that we don't end up storing anything personally identifiable.
+
+use std::{
+    fmt,
+    net::IpAddr,
+    str::FromStr,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            _ => Err("expected one of error, warn, info or debug".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        })
+    }
+}
+
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+static LOG_CLIENT_IPS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_log_level(level: LogLevel) {
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn set_log_client_ips(enabled: bool) {
+    LOG_CLIENT_IPS.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled(level: LogLevel) -> bool {
+    level as u8 <= LOG_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Write a log line, if `level` is enabled. Use the macros below instead of calling this.
+pub fn write(level: LogLevel, message: fmt::Arguments<'_>, fields: Map<String, Value>) {
+    if enabled(level) {
+        println!(
+            "{}",
+            format_line(SystemTime::now(), level, &message.to_string(), fields)
+        );
+    }
+}
+
+fn format_line(
+    time: SystemTime,
+    level: LogLevel,
+    message: &str,
+    fields: Map<String, Value>,
+) -> String {
+    let mut line = Map::new();
+    line.insert("timestamp".into(), format_timestamp(time).into());
+    line.insert("level".into(), level.to_string().into());
+    line.insert("message".into(), message.into());
+    line.extend(fields);
+    Value::Object(line).to_string()
+}
+
+/// Format `time` like `2024-03-04T20:27:42.123Z`.
+fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+    // Convert days since the epoch to a date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis(),
+    )
+}
+
+/// Middleware that logs one line per request, with the route rather than the full uri,
+/// so that query params don't end up in the logs.
+pub async fn log_requests(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("<unmatched>", MatchedPath::as_str)
+        .to_string();
+    let client_ip = LOG_CLIENT_IPS
+        .load(Ordering::Relaxed)
+        .then(|| crate::rate_limit::client_ip(&request))
+        .flatten();
+
+    let response = next.run(request).await;
+
+    let status = response.status().as_u16();
+    let level = if status >= 500 {
+        LogLevel::Warn
+    } else {
+        LogLevel::Info
+    };
+    write(
+        level,
+        format_args!("request"),
+        request_fields(method, route, status, start.elapsed(), client_ip),
+    );
+
+    response
+}
+
+fn request_fields(
+    method: String,
+    route: String,
+    status: u16,
+    latency: Duration,
+    client_ip: Option<IpAddr>,
+) -> Map<String, Value> {
+    let mut fields = Map::new();
+    fields.insert("method".into(), method.into());
+    fields.insert("route".into(), route.into());
+    fields.insert("status".into(), status.into());
+    fields.insert(
+        "latency_ms".into(),
+        (latency.as_micros() as f64 / 1000.0).into(),
+    );
+    if let Some(client_ip) = client_ip {
+        fields.insert("client_ip".into(), client_ip.to_string().into());
+    }
+    fields
+}
+
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => {
+        $crate::log::write($level, format_args!($($arg)*), Default::default())
+    };
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => { $crate::log::log!($crate::log::LogLevel::Error, $($arg)*) };
+}
+
+macro_rules! warning {
+    ($($arg:tt)*) => { $crate::log::log!($crate::log::LogLevel::Warn, $($arg)*) };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::log::log!($crate::log::LogLevel::Info, $($arg)*) };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => { $crate::log::log!($crate::log::LogLevel::Debug, $($arg)*) };
+}
+
+pub(crate) use {debug, error, info, log, warning};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_millis(1_709_584_062_123)),
+            "2024-03-04T20:27:42.123Z"
+        );
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "2000-02-29T00:00:00.000Z"
+        );
+    }
+
+    #[test]
+    fn test_format_request_line() {
+        let line = format_line(
+            UNIX_EPOCH,
+            LogLevel::Info,
+            "request",
+            request_fields(
+                "POST".to_string(),
+                "/record-install".to_string(),
+                200,
+                Duration::from_micros(1500),
+                None,
+            ),
+        );
+
+        assert_eq!(
+            line,
+            r#"{"latency_ms":1.5,"level":"info","message":"request","method":"POST","route":"/record-install","status":200,"timestamp":"1970-01-01T00:00:00.000Z"}"#
+        );
+    }
+}
@@ -1,90 +1,380 @@
 use std::net::SocketAddr;
-use std::{collections::BTreeMap, sync::LazyLock};
+use std::{collections::BTreeMap, process::ExitCode, sync::Arc, time::Duration};
 
 use axum::{
-    extract::Query,
-    response::Redirect,
+    extract::{Query, State},
+    http::{header, StatusCode},
+    middleware,
+    response::{IntoResponse, Redirect},
     routing::{get, post},
-    Router,
+    Json, Router,
 };
-use influxrs::{InfluxClient, Measurement};
+use serde::Deserialize;
 use tokio::net::TcpListener;
 
-static INFLUX_CLIENT: LazyLock<InfluxClient> = LazyLock::new(|| {
-    let url = get_env("INFLUX_URL");
-    let token = get_env("INFLUX_TOKEN");
-    let org = get_env("INFLUX_ORG");
-    InfluxClient::builder(url, token, org).build().unwrap()
-});
-
-static INFLUX_BUCKET: LazyLock<String> = LazyLock::new(|| get_env("INFLUX_BUCKET"));
-
-fn main() {
-    let rt = tokio::runtime::Runtime::new().unwrap();
-    let task = rt.spawn(async move {
-        let app = Router::new()
-            .route("/", get(root))
-            .route("/record-install", get(redirect_to_root))
-            .route("/record-install", post(record_install));
-
-        // Smoke test that we can write to influxdb before listening on the socket.
-        // This is a poor man's startup probe to avoid serving traffic before we can write to influxdb.
-        INFLUX_CLIENT
-            .write(
-                &INFLUX_BUCKET,
-                &[Measurement::builder("startups")
-                    .field("count", 1)
-                    .build()
-                    .unwrap()],
-            )
-            .await
-            .unwrap();
-        // ipv6 + ipv6 any addr
-        let addr = SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 0], 8080));
-        let listener = TcpListener::bind(addr).await.unwrap();
+mod backend;
+mod buffer;
+mod config;
+mod error;
+mod log;
+mod metrics;
+mod rate_limit;
+mod validate;
+
+#[cfg(test)]
+mod app_tests;
+
+use backend::{Backend, BackendError, Point, PopularCrate, PopularQuery};
+use buffer::{BufferConfig, WriteBuffer};
+use config::{Command, Config};
+use error::AppError;
+use log::{debug, error, info};
+use metrics::Metrics;
+use rate_limit::{rate_limit, RateLimiter};
+use validate::validate_tag;
+
+const DEFAULT_POPULAR_SINCE: Duration = Duration::from_secs(24 * 60 * 60);
+const DEFAULT_POPULAR_LIMIT: usize = 100;
+const MAX_POPULAR_LIMIT: usize = 1000;
+/// Further back than this, `/popular` gets expensive to query.
+const MAX_POPULAR_SINCE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Each client ip can record this many installs or build requests, or query
+/// `/popular` this many times, per minute.
+/// `cargo quickinstall --target all` can legitimately send one per target.
+const RATE_LIMIT_PER_MINUTE: u32 = 60;
+
+/// How long `/readyz` waits for the backend before giving up.
+const READINESS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long we wait for pending writes on shutdown. fly.io kills us after 5 seconds.
+const SHUTDOWN_FLUSH_TIMEOUT: Duration = Duration::from_secs(4);
+
+#[derive(Clone)]
+struct AppState {
+    backend: Arc<dyn Backend>,
+    buffer: WriteBuffer,
+    rate_limiter: Arc<RateLimiter>,
+    metrics: Arc<Metrics>,
+}
+
+fn main() -> ExitCode {
+    let config = match Command::from_args_and_env() {
+        Ok(Command::Run(config)) => config,
+        Ok(Command::Help) => {
+            print!("{}", config::HELP);
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("Error: {err}\n\nFor more information try --help");
+            return ExitCode::FAILURE;
+        }
+    };
+    log::set_log_level(config.log_level);
+    log::set_log_client_ips(config.log_client_ips);
+    rate_limit::set_trust_proxy_header(config.trust_proxy_header);
+
+    let result = tokio::runtime::Runtime::new()
+        .map_err(|err| format!("Failed to start the tokio runtime: {err}"))
+        .and_then(|rt| rt.block_on(run(config)));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            error!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(config: Config) -> Result<(), String> {
+    let backend = backend::open(&config.backend).map_err(|err| err.to_string())?;
 
-        axum::serve(listener, app).await.unwrap();
+    // Smoke test that we can write to the backend before listening on the socket.
+    // This is a poor man's startup probe to avoid serving traffic before we can write to influxdb.
+    backend
+        .write(&[Point::new("startups")])
+        .await
+        .map_err(|err| format!("Failed to write to the backend on startup: {err}"))?;
+
+    let (buffer, flusher) = WriteBuffer::spawn(backend.clone(), BufferConfig::default());
+    let app = app(AppState {
+        backend,
+        buffer,
+        rate_limiter: Arc::new(RateLimiter::new(
+            RATE_LIMIT_PER_MINUTE,
+            Duration::from_secs(60),
+        )),
+        metrics: Arc::default(),
     });
-    rt.block_on(task).unwrap();
+
+    let listener = TcpListener::bind(config.bind)
+        .await
+        .map_err(|err| format!("Failed to listen on {}: {err}", config.bind))?;
+    info!("Listening on {}", config.bind);
+
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .map_err(|err| format!("Server error: {err}"))?;
+
+    // The server has dropped its `WriteBuffer`s by now, so the flusher writes what's
+    // left and exits.
+    info!("Flushing pending writes");
+    match tokio::time::timeout(SHUTDOWN_FLUSH_TIMEOUT, flusher).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(err)) => Err(format!("The flusher panicked: {err}")),
+        Err(_) => Err("Timed out flushing pending writes".to_string()),
+    }
 }
 
-async fn root() -> &'static str {
-    "This is the stats server for cargo-quickinstall. Go to https://github.com/cargo-bins/cargo-quickinstall for more information."
+/// Resolves on SIGTERM or SIGINT (ctrl-c), to start a graceful shutdown.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.ok();
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(err) => {
+                error!("Failed to listen for SIGTERM: {err}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    info!("Shutting down");
+}
+
+fn app(state: AppState) -> Router {
+    let rate_limited = Router::new()
+        .route("/record-install", post(record_install))
+        .route("/request-build", post(request_build))
+        .route("/popular", get(popular))
+        .route_layer(middleware::from_fn_with_state(
+            state.rate_limiter.clone(),
+            rate_limit,
+        ));
+
+    Router::new()
+        .route("/", get(root))
+        .route("/record-install", get(redirect_to_root))
+        .route("/request-build", get(redirect_to_root))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
+        .merge(rate_limited)
+        .layer(middleware::from_fn_with_state(
+            state.metrics.clone(),
+            metrics::count_requests,
+        ))
+        .layer(middleware::from_fn(log::log_requests))
+        .with_state(state)
 }
 
-fn get_env(key: &str) -> String {
-    std::env::var(key).unwrap_or_else(|_| panic!("{key} must be set"))
+async fn root() -> &'static str {
+    "This is the stats server for cargo-quickinstall. Go to https://github.com/cargo-bins/cargo-quickinstall for more information."
 }
 
 async fn redirect_to_root() -> Redirect {
     Redirect::to("/")
 }
 
-async fn record_install(Query(params): Query<BTreeMap<String, String>>) -> String {
-    println!("Hi there {params:?}");
+/// The process is up. We don't check the backend here, so that a backend outage doesn't
+/// get us restarted.
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// We can talk to the backend, so it is worth sending us traffic.
+async fn readyz(State(state): State<AppState>) -> Result<&'static str, AppError> {
+    match tokio::time::timeout(READINESS_TIMEOUT, state.backend.ping()).await {
+        Ok(result) => result?,
+        Err(_) => return Err(AppError::Backend(BackendError("Timed out".to_string()))),
+    }
+    Ok("ok")
+}
+
+async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(&state.buffer.stats),
+    )
+}
+
+async fn record_install(
+    State(state): State<AppState>,
+    Query(params): Query<BTreeMap<String, String>>,
+) -> Result<&'static str, AppError> {
+    let result = write_point(
+        &state,
+        "counts",
+        &["crate", "version", "target", "agent", "status"],
+        &params,
+    )
+    .await;
 
-    let mut point = Measurement::builder("counts").field("count", 1);
-    for (tag, value) in &params {
-        if !["crate", "version", "target", "agent", "status"].contains(&tag.as_str()) {
-            println!("Skipping unknown query param: {tag}={value}");
+    let label = |key: &str| {
+        result
+            .is_ok()
+            .then(|| params.get(key))
+            .flatten()
+            .map(String::as_str)
+    };
+    let code = match &result {
+        Ok(()) => StatusCode::OK,
+        Err(err) => err.status_code(),
+    };
+    state
+        .metrics
+        .record_install(code.as_u16(), label("target"), label("status"));
+
+    result?;
+    Ok("Recorded")
+}
+
+/// Explicit requests from `cargo quickinstall request`, kept separate from the install
+/// counts so that the cronjob can build these first.
+async fn request_build(
+    State(state): State<AppState>,
+    Query(params): Query<BTreeMap<String, String>>,
+) -> Result<&'static str, AppError> {
+    write_point(
+        &state,
+        "build-requests",
+        &["crate", "version", "target", "agent"],
+        &params,
+    )
+    .await?;
+    Ok("Build requested")
+}
+
+/// Tags that every point must have, so that we can attribute it to a package.
+const REQUIRED_TAGS: &[&str] = &["crate", "version", "target"];
+
+async fn write_point(
+    state: &AppState,
+    measurement: &'static str,
+    tags: &[&str],
+    params: &BTreeMap<String, String>,
+) -> Result<(), AppError> {
+    let mut point = Point::new(measurement);
+    for (tag, value) in params {
+        if !tags.contains(&tag.as_str()) {
+            // Only log the name, in case someone sends us something they shouldn't have.
+            debug!("Skipping unknown query param: {tag}");
             continue;
         }
-        point = point.tag(tag, value.as_str())
+        validate_tag(tag, value)
+            .map_err(|reason| AppError::BadRequest(format!("Invalid `{tag}`: {reason}")))?;
+        point = point.tag(tag, value)
     }
-    INFLUX_CLIENT
-        .write(&INFLUX_BUCKET, &[point.build().unwrap()])
-        .await
-        .unwrap();
-    format!("Hi there {params:?}")
+    if let Some(missing) = REQUIRED_TAGS
+        .iter()
+        .find(|tag| !point.tags.contains_key(**tag))
+    {
+        return Err(AppError::BadRequest(format!("Missing `{missing}`")));
+    }
+    state.buffer.push(point).map_err(|_| AppError::BufferFull)
+}
+
+#[derive(Deserialize)]
+struct PopularParams {
+    target: Option<String>,
+    since: Option<String>,
+    limit: Option<usize>,
+}
+
+/// The most installed crate versions, for the cronjob to decide what to build next
+/// without needing InfluxDB credentials.
+///
+/// Takes an optional `target`, `since` (like `1h`, `1d` or `7d`, default `1d`, at most
+/// `30d`) and `limit`.
+async fn popular(
+    State(state): State<AppState>,
+    Query(params): Query<PopularParams>,
+) -> Result<Json<Vec<PopularCrate>>, AppError> {
+    if let Some(target) = &params.target {
+        validate_tag("target", target)
+            .map_err(|reason| AppError::BadRequest(format!("Invalid `target`: {reason}")))?;
+    }
+    let since = match &params.since {
+        Some(since) => parse_duration(since).ok_or_else(|| {
+            AppError::BadRequest("Invalid `since`: expected a duration like `1d`".to_string())
+        })?,
+        None => DEFAULT_POPULAR_SINCE,
+    };
+    if since > MAX_POPULAR_SINCE {
+        return Err(AppError::BadRequest(
+            "Invalid `since`: can be at most `30d`".to_string(),
+        ));
+    }
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_POPULAR_LIMIT)
+        .min(MAX_POPULAR_LIMIT);
+
+    let query = PopularQuery {
+        target: params.target,
+        since,
+        limit,
+    };
+    Ok(Json(state.backend.popular(&query).await?))
+}
+
+/// Parse durations like `30m`, `12h`, `1d` or `2w`.
+fn parse_duration(s: &str) -> Option<Duration> {
+    let unit_index = s.find(|c: char| !c.is_ascii_digit())?;
+    let (number, unit) = s.split_at(unit_index);
+    let number: u64 = number.parse().ok()?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    number.checked_mul(unit_secs).map(Duration::from_secs)
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
+    use std::{
+        collections::BTreeMap,
+        sync::{atomic::Ordering, Arc},
+        time::Duration,
+    };
 
-    use axum::extract::Query;
+    use axum::extract::{Query, State};
 
-    use crate::record_install;
+    use crate::{
+        backend::InfluxBackend, parse_duration, record_install, AppState, BufferConfig,
+        RateLimiter, WriteBuffer,
+    };
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s").unwrap().as_secs(), 90);
+        assert_eq!(parse_duration("1d").unwrap().as_secs(), 86400);
+        assert_eq!(parse_duration("2w").unwrap().as_secs(), 14 * 86400);
+        assert_eq!(parse_duration("1"), None);
+        assert_eq!(parse_duration("d"), None);
+        assert_eq!(parse_duration("1y"), None);
+    }
 
     #[tokio::test]
     async fn smoke_test_against_real_server() {
@@ -92,11 +382,43 @@ mod tests {
             println!("set INFLUX_URL, INFLUX_ORG and INFLUX_TOKEN to enable this test");
             return;
         }
-        record_install(Query(
-            [("x".to_string(), "y".to_string())]
+        let env = |key: &str| std::env::var(key).unwrap();
+        let backend = Arc::new(
+            InfluxBackend::new(
+                env("INFLUX_URL"),
+                env("INFLUX_TOKEN"),
+                env("INFLUX_ORG"),
+                env("INFLUX_BUCKET"),
+            )
+            .unwrap(),
+        );
+        let (buffer, flusher) = WriteBuffer::spawn(backend.clone(), BufferConfig::default());
+        let stats = buffer.stats.clone();
+
+        record_install(
+            State(AppState {
+                backend,
+                buffer,
+                rate_limiter: Arc::new(RateLimiter::new(1, Duration::from_secs(60))),
+                metrics: Arc::default(),
+            }),
+            Query(
+                [
+                    ("crate", "cargo-quickinstall"),
+                    ("version", "0.0.0"),
+                    ("target", "x86_64-unknown-linux-gnu"),
+                    ("agent", "stats-server-smoke-test"),
+                ]
                 .into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
                 .collect::<BTreeMap<String, String>>(),
-        ))
-        .await;
+            ),
+        )
+        .await
+        .unwrap();
+
+        // Dropping the state closes the buffer, which makes the flusher write everything.
+        flusher.await.unwrap();
+        assert_eq!(stats.written.load(Ordering::Relaxed), 1);
     }
 }
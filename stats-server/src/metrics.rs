@@ -0,0 +1,208 @@
+//! Counters for the `/metrics` endpoint, rendered in the Prometheus text format by hand
+//! because we only need a handful of them.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::buffer::BufferStats;
+
+/// Upper bounds of the backend write latency buckets, in seconds.
+const LATENCY_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 10.0];
+
+#[derive(Debug, Default)]
+pub struct Histogram {
+    /// Non-cumulative counts for each of `LATENCY_BUCKETS`, plus one for `+Inf`.
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    pub fn observe(&self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| secs <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str) {
+        let mut cumulative = 0;
+        for (i, count) in self.buckets.iter().enumerate() {
+            cumulative += count.load(Ordering::Relaxed);
+            match LATENCY_BUCKETS.get(i) {
+                Some(bound) => writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {cumulative}"),
+                None => writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {cumulative}"),
+            }
+            .unwrap();
+        }
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        writeln!(out, "{name}_sum {sum}").unwrap();
+        writeln!(out, "{name}_count {cumulative}").unwrap();
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Every response, by (route, http status code). Requests that didn't match a route
+    /// are counted together, so that scanners can't create new series.
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+    /// Responses to `/record-install`, by (http status code, target, install status).
+    /// The labels are only filled in for requests that passed validation, so that there
+    /// is a bounded number of them.
+    installs: Mutex<BTreeMap<(u16, String, String), u64>>,
+}
+
+impl Metrics {
+    pub fn record_request(&self, route: &str, code: u16) {
+        let key = (route.to_string(), code);
+        *self.requests.lock().unwrap().entry(key).or_default() += 1;
+    }
+
+    pub fn record_install(&self, code: u16, target: Option<&str>, status: Option<&str>) {
+        let key = (
+            code,
+            target.unwrap_or("").to_string(),
+            status.unwrap_or("").to_string(),
+        );
+        *self.installs.lock().unwrap().entry(key).or_default() += 1;
+    }
+
+    pub fn render(&self, buffer: &BufferStats) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP stats_http_requests_total Requests, by route and status code.\n");
+        out.push_str("# TYPE stats_http_requests_total counter\n");
+        for ((route, code), count) in self.requests.lock().unwrap().iter() {
+            writeln!(
+                out,
+                "stats_http_requests_total{{route=\"{route}\",code=\"{code}\"}} {count}"
+            )
+            .unwrap();
+        }
+
+        out.push_str("# HELP stats_record_install_requests_total Requests to /record-install.\n");
+        out.push_str("# TYPE stats_record_install_requests_total counter\n");
+        for ((code, target, status), count) in self.installs.lock().unwrap().iter() {
+            writeln!(
+                out,
+                "stats_record_install_requests_total{{code=\"{code}\",target=\"{target}\",status=\"{status}\"}} {count}"
+            )
+            .unwrap();
+        }
+
+        let counters = [
+            (
+                "stats_buffer_queue_depth",
+                "gauge",
+                "Points waiting to be written to the backend.",
+                buffer.queue_depth.load(Ordering::Relaxed) as u64,
+            ),
+            (
+                "stats_buffer_dropped_points_total",
+                "counter",
+                "Points dropped because the buffer was full or the write failed.",
+                buffer.dropped.load(Ordering::Relaxed),
+            ),
+            (
+                "stats_backend_written_points_total",
+                "counter",
+                "Points written to the backend.",
+                buffer.written.load(Ordering::Relaxed),
+            ),
+            (
+                "stats_backend_write_errors_total",
+                "counter",
+                "Failed batch writes to the backend.",
+                buffer.write_errors.load(Ordering::Relaxed),
+            ),
+        ];
+        for (name, kind, help, value) in counters {
+            writeln!(out, "# HELP {name} {help}").unwrap();
+            writeln!(out, "# TYPE {name} {kind}").unwrap();
+            writeln!(out, "{name} {value}").unwrap();
+        }
+
+        let name = "stats_backend_write_duration_seconds";
+        writeln!(
+            out,
+            "# HELP {name} Time taken to write a batch to the backend."
+        )
+        .unwrap();
+        writeln!(out, "# TYPE {name} histogram").unwrap();
+        buffer.write_latency.render(&mut out, name);
+
+        out
+    }
+}
+
+/// Middleware that counts every response in [`Metrics`], including the ones from the
+/// rate limiter, so it has to be layered outside of it.
+pub async fn count_requests(
+    State(metrics): State<Arc<Metrics>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("<unmatched>", MatchedPath::as_str)
+        .to_string();
+
+    let response = next.run(request).await;
+
+    metrics.record_request(&route, response.status().as_u16());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        let target = Some("x86_64-unknown-linux-gnu");
+        metrics.record_install(200, target, Some("built-from-source"));
+        metrics.record_install(200, target, Some("built-from-source"));
+        metrics.record_install(200, target, None);
+        metrics.record_install(400, None, None);
+        metrics.record_request("/request-build", 429);
+
+        let buffer = BufferStats::default();
+        buffer.queue_depth.store(3, Ordering::Relaxed);
+        buffer.write_latency.observe(Duration::from_millis(20));
+        buffer.write_latency.observe(Duration::from_secs(60));
+
+        let rendered = metrics.render(&buffer);
+
+        for line in [
+            r#"stats_record_install_requests_total{code="200",target="x86_64-unknown-linux-gnu",status="built-from-source"} 2"#,
+            r#"stats_record_install_requests_total{code="200",target="x86_64-unknown-linux-gnu",status=""} 1"#,
+            r#"stats_record_install_requests_total{code="400",target="",status=""} 1"#,
+            r#"stats_http_requests_total{route="/request-build",code="429"} 1"#,
+            "stats_buffer_queue_depth 3",
+            r#"stats_backend_write_duration_seconds_bucket{le="0.01"} 0"#,
+            r#"stats_backend_write_duration_seconds_bucket{le="0.025"} 1"#,
+            r#"stats_backend_write_duration_seconds_bucket{le="+Inf"} 2"#,
+            "stats_backend_write_duration_seconds_count 2",
+        ] {
+            assert!(rendered.lines().any(|l| l == line), "{line}\n{rendered}");
+        }
+    }
+}
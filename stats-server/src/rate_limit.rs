@@ -0,0 +1,149 @@
+//! Per-ip rate limiting, so that one misbehaving client can't flood the stats.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::AppError;
+
+/// Once we are tracking this many ips, forget the ones whose window has expired.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Whether to believe the `Fly-Client-IP` header, see `--trust-proxy-header`.
+static TRUST_PROXY_HEADER: AtomicBool = AtomicBool::new(false);
+
+pub fn set_trust_proxy_header(enabled: bool) {
+    TRUST_PROXY_HEADER.store(enabled, Ordering::Relaxed);
+}
+
+/// Allows each ip `max_requests` per `window`, using fixed windows.
+pub struct RateLimiter {
+    max_requests: u32,
+    window: Duration,
+    windows: Mutex<HashMap<IpAddr, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count a request from `ip`, returning whether it is allowed.
+    pub fn check(&self, ip: IpAddr, now: Instant) -> bool {
+        let mut windows = self.windows.lock().unwrap();
+
+        if windows.len() >= PRUNE_THRESHOLD {
+            windows.retain(|_, (start, _)| now.duration_since(*start) < self.window);
+        }
+
+        let (start, count) = windows.entry(ip).or_insert((now, 0));
+        if now.duration_since(*start) >= self.window {
+            *start = now;
+            *count = 0;
+        }
+        *count += 1;
+        *count <= self.max_requests
+    }
+}
+
+/// The ip of the client, from the socket, or from the `Fly-Client-IP` header with
+/// `--trust-proxy-header`.
+pub(crate) fn client_ip(request: &Request) -> Option<IpAddr> {
+    client_ip_with(request, TRUST_PROXY_HEADER.load(Ordering::Relaxed))
+}
+
+/// Behind fly.io's proxy, every connection comes from the proxy, which tells us
+/// who the client is with `Fly-Client-IP`. Anywhere else, clients could use that
+/// header to pick their own ip and dodge the rate limit.
+fn client_ip_with(request: &Request, trust_proxy_header: bool) -> Option<IpAddr> {
+    let from_header = || {
+        request
+            .headers()
+            .get("fly-client-ip")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+    };
+
+    trust_proxy_header.then(from_header).flatten().or_else(|| {
+        request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+    })
+}
+
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(ip) = client_ip(&request) {
+        if !limiter.check(ip, Instant::now()) {
+            return AppError::TooManyRequests.into_response();
+        }
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let alice = IpAddr::from([192, 0, 2, 1]);
+        let bob = IpAddr::from([192, 0, 2, 2]);
+        let start = Instant::now();
+
+        assert!(limiter.check(alice, start));
+        assert!(limiter.check(alice, start));
+        assert!(!limiter.check(alice, start + Duration::from_secs(59)));
+        assert!(limiter.check(bob, start + Duration::from_secs(59)));
+
+        assert!(limiter.check(alice, start + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let mut request = Request::builder()
+            .header("fly-client-ip", "192.0.2.1")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(client_ip_with(&request, false), None);
+        assert_eq!(
+            client_ip_with(&request, true),
+            Some(IpAddr::from([192, 0, 2, 1]))
+        );
+
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([198, 51, 100, 1], 1234))));
+        assert_eq!(
+            client_ip_with(&request, false),
+            Some(IpAddr::from([198, 51, 100, 1]))
+        );
+        assert_eq!(
+            client_ip_with(&request, true),
+            Some(IpAddr::from([192, 0, 2, 1]))
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Checks for the query params that clients send us, so that we only store tags that
+//! we know how to query, and so that nobody can fill the database with junk.
+
+/// The targets that we build packages for.
+///
+/// This must be kept in sync with the `supported-targets` file at the root of the repo.
+/// We can't read it at build time because the docker build only sees this directory.
+pub const SUPPORTED_TARGETS: &[&str] = &[
+    "x86_64-pc-windows-msvc",
+    "x86_64-apple-darwin",
+    "aarch64-apple-darwin",
+    "x86_64-unknown-linux-gnu",
+    "x86_64-unknown-linux-musl",
+    "aarch64-unknown-linux-gnu",
+    "aarch64-unknown-linux-musl",
+    "aarch64-pc-windows-msvc",
+    "armv7-unknown-linux-musleabihf",
+    "armv7-unknown-linux-gnueabihf",
+];
+
+/// Statuses reported by `install_result_to_status_str()` in cargo-quickinstall, and by
+/// the quickinstall fetcher in cargo-binstall.
+pub const KNOWN_STATUSES: &[&str] = &[
+    // cargo-quickinstall
+    "installed-from-tarball",
+    "built-from-source",
+    "already-installed",
+    "cargo-install-failed",
+    "no-fallback",
+    "crate-not-found",
+    "version-lookup-failed",
+    "target-detection-failed",
+    "download-failed",
+    "extract-failed",
+    "binary-collision",
+    "invalid-json",
+    "io-error",
+    "other-error",
+    // cargo-binstall, see `Status` in binstalk-fetchers' quickinstall.rs
+    "start",
+    "not-found",
+    "found",
+    "attempting-install",
+    "invalid-signature",
+    "failed-to-install",
+];
+
+const MAX_CRATE_NAME_LEN: usize = 64;
+const MAX_VERSION_LEN: usize = 64;
+const MAX_AGENT_LEN: usize = 64;
+
+/// Check the value of one of the tags that we store, returning a reason if it is invalid.
+pub fn validate_tag(key: &str, value: &str) -> Result<(), &'static str> {
+    match key {
+        "crate" => validate_crate_name(value),
+        "version" => validate_version(value),
+        "target" => validate_target(value),
+        "agent" => validate_agent(value),
+        "status" => validate_status(value),
+        _ => Err("unknown tag"),
+    }
+}
+
+/// Same rules as crates.io: ascii alphanumerics, `-` and `_`, starting with a letter.
+fn validate_crate_name(name: &str) -> Result<(), &'static str> {
+    if name.is_empty() || name.len() > MAX_CRATE_NAME_LEN {
+        return Err("crate name must be between 1 and 64 characters");
+    }
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic())
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("crate name must be ascii alphanumerics, `-` and `_`");
+    }
+    Ok(())
+}
+
+/// Loosely semver: `1.2.3`, `1.0.0-rc.1` and `1.0.0+build` are all fine.
+fn validate_version(version: &str) -> Result<(), &'static str> {
+    if version.is_empty() || version.len() > MAX_VERSION_LEN {
+        return Err("version must be between 1 and 64 characters");
+    }
+    if !version.starts_with(|c: char| c.is_ascii_digit())
+        || !version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'))
+    {
+        return Err("version must look like semver");
+    }
+    Ok(())
+}
+
+fn validate_target(target: &str) -> Result<(), &'static str> {
+    if !SUPPORTED_TARGETS.contains(&target) {
+        return Err("target is not supported");
+    }
+    Ok(())
+}
+
+/// Agents look like `cargo-quickinstall/0.3.0` or `binstall/1.10.0`.
+fn validate_agent(agent: &str) -> Result<(), &'static str> {
+    if agent.is_empty() || agent.len() > MAX_AGENT_LEN {
+        return Err("agent must be between 1 and 64 characters");
+    }
+    if !agent
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '+' | '/'))
+    {
+        return Err("agent must be ascii alphanumerics and `.-_+/`");
+    }
+    Ok(())
+}
+
+fn validate_status(status: &str) -> Result<(), &'static str> {
+    if !KNOWN_STATUSES.contains(&status) {
+        return Err("status is not known");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supported_targets_in_sync() {
+        let supported_targets = include_str!("../../supported-targets");
+
+        assert_eq!(
+            supported_targets.split_whitespace().collect::<Vec<_>>(),
+            SUPPORTED_TARGETS
+        );
+    }
+
+    #[test]
+    fn test_validate_tag() {
+        for (key, value) in [
+            ("crate", "cargo-quickinstall"),
+            ("crate", "fd_find"),
+            ("version", "14.1.1"),
+            ("version", "1.0.0-rc.1+build.5"),
+            ("target", "x86_64-unknown-linux-gnu"),
+            ("agent", "cargo-quickinstall/0.3.0"),
+            ("agent", "binstall/1.10.0"),
+            ("status", "built-from-source"),
+            ("status", "download-failed"),
+            ("status", "attempting-install"),
+            ("status", "failed-to-install"),
+        ] {
+            assert_eq!(validate_tag(key, value), Ok(()), "{key}={value}");
+        }
+
+        for (key, value) in [
+            ("crate", ""),
+            ("crate", "1password"),
+            ("crate", "ripgrep; DROP TABLE points"),
+            ("crate", &"a".repeat(65)),
+            ("version", "latest"),
+            ("version", "1.0.0 "),
+            ("target", "x86_64-unknown-freebsd"),
+            ("agent", "curl/8.0.0 (linux)"),
+            ("status", "made-up"),
+            ("other", "x"),
+        ] {
+            assert!(validate_tag(key, value).is_err(), "{key}={value}");
+        }
+    }
+}